@@ -1,3 +1,11 @@
+use logos::Logos;
+
+use crate::{
+    lexer::Token,
+    span::{Span, mark},
+    typeck::Ty,
+};
+
 pub trait ToRust {
     fn to_rust(&self) -> String;
 }
@@ -30,49 +38,165 @@ impl<T: ToRust> JoinToRust for Vec<T> {
     }
 }
 
+/// Whether `op` assigns to its left-hand side, such as `=` or `+=`
+pub fn is_assignment(op: &str) -> bool {
+    matches!(
+        op,
+        "=" | "+=" | "-=" | "*=" | "/=" | "%=" | "**=" | "&=" | "|=" | "~=" | "^=" | "<<=" | "<|="
+    )
+}
+
+/// Whether `name` is written like a constant, such as `MAX_SIZE`
+fn is_const_name(name: &str) -> bool {
+    let mut lex = Token::lexer(name);
+    lex.next() == Some(Ok(Token::ConstIdentifier)) && lex.next().is_none()
+}
+
+fn precedence(op: &str) -> u8 {
+    Token::lexer(op)
+        .next()
+        .and_then(|tok| tok.ok())
+        .map_or(0, |tok| tok.get_precedence())
+}
+
 #[derive(Clone)]
 pub enum Literal {
-    Integer(i64),
+    Integer(i128),
+    /// An integer above `i128::MAX`, which only fits in `N128`
+    Unsigned(u128),
     Float(f64),
     String(String),
-}
-
-impl Literal {
-    fn to_rust_type(&self) -> String {
-        match self {
-            Literal::Integer(_) => String::from("i64"),
-            Literal::Float(_) => String::from("f64"),
-            Literal::String(_) => String::from("String"),
-        }
-    }
+    /// Only produced by evaluating a constant, such as `1 < 2`
+    Bool(bool),
 }
 
 impl ToRust for Literal {
     fn to_rust(&self) -> String {
         match self {
             Literal::Integer(i) => i.to_string(),
-            Literal::Float(f) => f.to_string(),
+            Literal::Unsigned(u) => u.to_string(),
+            Literal::Float(f) => format!("{:?}", f),
             Literal::String(s) => format!("format!(\"{}\")", s),
+            Literal::Bool(b) => b.to_string(),
         }
     }
 }
 
-pub enum Pattern {
+#[derive(Clone)]
+pub struct Pattern {
+    pub kind: PatternKind,
+    pub span: Span,
+}
+
+impl Pattern {
+    pub fn new(kind: PatternKind, span: Span) -> Self {
+        Pattern { kind, span }
+    }
+}
+
+#[derive(Clone)]
+pub enum PatternKind {
     Literal(Literal),
     Variable(String),
     List(Vec<Pattern>),
+    Tuple(Vec<Pattern>),
+    /// `<enum>.<variant>` with optional `(<patterns>)`
+    Variant(String, String, Vec<Pattern>),
+    /// `<enum>.<variant>{<fields>}`, binding only the named fields
+    StructVariant(String, String, Vec<String>),
     Wildcard,
 }
 
+impl Pattern {
+    /// Same as [`ToRust::to_rust`] but binds every variable mutably
+    fn to_rust_mutable(&self) -> String {
+        match &self.kind {
+            PatternKind::Variable(name) => format!("mut {}", name.to_rust()),
+            PatternKind::List(patterns) => format!(
+                "[{}]",
+                patterns
+                    .iter()
+                    .map(|p| p.to_rust_mutable())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+            PatternKind::Tuple(patterns) => format!(
+                "({})",
+                patterns
+                    .iter()
+                    .map(|p| p.to_rust_mutable())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+            _ => self.to_rust(),
+        }
+    }
+}
+
 impl ToRust for Pattern {
     fn to_rust(&self) -> String {
-        match self {
-            Pattern::Literal(Literal::String(lit)) => format!("\"{}\"", lit),
-            Pattern::Literal(lit) => lit.to_rust(),
-            Pattern::Variable(name) => name.to_rust(),
-            Pattern::List(patterns) => format!("[{}]", patterns.to_rust(",")),
-            Pattern::Wildcard => String::from("_"),
-        }
+        let rust = match &self.kind {
+            PatternKind::Literal(Literal::String(lit)) => format!("\"{}\"", lit),
+            PatternKind::Literal(lit) => lit.to_rust(),
+            PatternKind::Variable(name) => name.to_rust(),
+            PatternKind::List(patterns) => format!("[{}]", patterns.to_rust(",")),
+            PatternKind::Tuple(patterns) => format!("({})", patterns.to_rust(",")),
+            PatternKind::Variant(enum_name, variant, patterns) if patterns.is_empty() => {
+                format!("{}::{}", enum_name.to_rust(), variant.to_rust())
+            }
+            PatternKind::Variant(enum_name, variant, patterns) => format!(
+                "{}::{}({})",
+                enum_name.to_rust(),
+                variant.to_rust(),
+                patterns.to_rust(",")
+            ),
+            PatternKind::StructVariant(enum_name, variant, fields) => format!(
+                "{}::{}{{{},..}}",
+                enum_name.to_rust(),
+                variant.to_rust(),
+                fields.to_rust(",")
+            ),
+            PatternKind::Wildcard => String::from("_"),
+        };
+        mark(&self.span, rust)
+    }
+}
+
+/// Prefix for a value matched against `patterns`, since string literal
+/// and list patterns only match on `&str` and slices
+fn deref_for<'a>(mut patterns: impl Iterator<Item = &'a Pattern>) -> &'static str {
+    if patterns.any(|p| {
+        matches!(
+            p.kind,
+            PatternKind::Literal(Literal::String(_)) | PatternKind::List(_)
+        )
+    }) {
+        "&*"
+    } else {
+        ""
+    }
+}
+
+/// `<pattern> [<- <guard>] => <body>`
+#[derive(Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Expr>,
+    pub body: Vec<Expr>,
+    pub span: Span,
+}
+
+impl ToRust for MatchArm {
+    fn to_rust(&self) -> String {
+        let rust = format!(
+            "{}{} => {{{}}}",
+            self.pattern.to_rust(),
+            self.guard
+                .as_ref()
+                .map_or(String::new(), |guard| format!(" if {}", guard.to_rust())),
+            self.body.to_rust(";")
+        );
+        mark(&self.span, rust)
     }
 }
 
@@ -94,14 +218,32 @@ impl ToRust for AssignType {
 }
 
 #[derive(Clone)]
-pub enum Expr {
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+    /// Type inferred by [`crate::typeck::check`], if it has run
+    pub ty: Option<Ty>,
+}
+
+#[derive(Clone)]
+pub enum ExprKind {
     This,
     None,
     Reference(Box<Expr>),
     List(Vec<Expr>),
+    Tuple(Vec<Expr>),
+    /// `{<key> => <value> ...}`
+    Map(Vec<(Expr, Expr)>),
+    /// `{<item> ...}`
+    Set(Vec<Expr>),
+    /// `<map>{<key>}`
+    Entry {
+        map: Box<Expr>,
+        key: Box<Expr>,
+    },
     Literal(Literal),
     Variable(String),
-    /// Always contain [`Expr::Variable`] or [`Expr::Call`] or [`Expr::None`].
+    /// Always contain [`ExprKind::Variable`] or [`ExprKind::Call`] or [`ExprKind::None`].
     AnonParam(Box<Expr>),
     Some(Box<Expr>),
     Ok(Box<Expr>),
@@ -121,8 +263,14 @@ pub enum Expr {
         callee: String,
         args: Vec<Expr>,
     },
+    /// `<enum>.<variant>` with optional `(<args>)`
+    Variant {
+        enum_name: String,
+        variant: String,
+        args: Vec<Expr>,
+    },
     Assign {
-        name: String,
+        pattern: Pattern,
         assign_type: AssignType,
         mutable: bool,
         value: Box<Expr>,
@@ -132,76 +280,232 @@ pub enum Expr {
         args: Vec<String>,
         body: Box<Expr>,
     },
+    /// `<pattern> @ <iterable>` followed by an indented body
+    For {
+        pattern: Pattern,
+        iterable: Box<Expr>,
+        body: Vec<Expr>,
+    },
+    /// `@ <- <condition>` followed by an indented body
+    While {
+        condition: Box<Expr>,
+        body: Vec<Expr>,
+    },
+    /// `@` followed by an indented body
+    Loop(Vec<Expr>),
+    /// `<subject> ?` followed by indented arms
+    Match {
+        subject: Box<Expr>,
+        arms: Vec<MatchArm>,
+    },
+    Break(Option<Box<Expr>>),
+    Continue,
 }
 
 impl Expr {
-    fn to_rust_type(&self) -> String {
-        match self {
-            Expr::This => String::from("Self"),
-            Expr::None => String::from("Option<_>"),
-            Expr::Reference(inner) => format!("&{}", inner.to_rust_type()),
-            Expr::List(items) => format!(
-                "Vec<{}>",
-                items
-                    .first()
-                    .map_or(String::from("_"), |item| item.to_rust_type())
-            ),
-            Expr::Literal(lit) => lit.to_rust_type(),
-            Expr::Variable(_) => String::from("_"),
-            Expr::AnonParam(param) => param.to_rust_type(),
-            Expr::Some(expr) => format!("Option<{}>", expr.to_rust_type()),
-            Expr::Ok(expr) => format!("Result<{}, _>", expr.to_rust_type()),
-            Expr::Err(expr) => format!("Result<_, {}>", expr.to_rust_type()),
-            Expr::Binary { op, lhs, rhs } => match op.as_str() {
-                "==" | "!=" | "<" | "<=" | ">" | ">=" | "&&" | "||" => String::from("bool"),
-                "<<" | "<|" => String::from("()"),
-                "." => rhs.to_rust_type(),
-                ".." => format!("std::ops::Range<{}>", lhs.to_rust_type()),
-                _ => lhs.to_rust_type(),
-            },
-            Expr::Ternary { if_true, .. } => if_true.to_rust_type(),
-            Expr::Call { .. } => String::from("_"),
-            Expr::Assign {
-                value, type_hint, ..
-            } => {
-                if let Some(hint) = type_hint {
-                    hint.to_rust()
-                } else {
-                    value.to_rust_type()
-                }
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Expr {
+            kind,
+            span,
+            ty: None,
+        }
+    }
+
+    /// Every expression directly inside this one, such as the operands of a binary expression
+    pub fn children(&self) -> Vec<&Expr> {
+        match &self.kind {
+            ExprKind::This
+            | ExprKind::None
+            | ExprKind::Literal(_)
+            | ExprKind::Variable(_)
+            | ExprKind::Break(None)
+            | ExprKind::Continue => vec![],
+            ExprKind::Reference(inner)
+            | ExprKind::AnonParam(inner)
+            | ExprKind::Some(inner)
+            | ExprKind::Ok(inner)
+            | ExprKind::Err(inner)
+            | ExprKind::Break(Some(inner))
+            | ExprKind::Closure { body: inner, .. }
+            | ExprKind::Assign { value: inner, .. } => vec![inner],
+            ExprKind::List(items)
+            | ExprKind::Tuple(items)
+            | ExprKind::Set(items)
+            | ExprKind::Loop(items)
+            | ExprKind::Call { args: items, .. }
+            | ExprKind::Variant { args: items, .. } => items.iter().collect(),
+            ExprKind::Map(entries) => entries.iter().flat_map(|(k, v)| [k, v]).collect(),
+            ExprKind::Entry { map: lhs, key: rhs } | ExprKind::Binary { lhs, rhs, .. } => {
+                vec![lhs, rhs]
+            }
+            ExprKind::Ternary {
+                condition,
+                if_true,
+                if_false,
+            } => vec![condition, if_true, if_false],
+            ExprKind::For {
+                iterable: first,
+                body,
+                ..
+            }
+            | ExprKind::While {
+                condition: first,
+                body,
+            } => std::iter::once(&**first).chain(body).collect(),
+            ExprKind::Match { subject, arms } => std::iter::once(&**subject)
+                .chain(
+                    arms.iter()
+                        .flat_map(|arm| arm.guard.iter().chain(&arm.body)),
+                )
+                .collect(),
+        }
+    }
+
+    /// Same as [`Expr::children`] but mutable
+    pub fn children_mut(&mut self) -> Vec<&mut Expr> {
+        match &mut self.kind {
+            ExprKind::This
+            | ExprKind::None
+            | ExprKind::Literal(_)
+            | ExprKind::Variable(_)
+            | ExprKind::Break(None)
+            | ExprKind::Continue => vec![],
+            ExprKind::Reference(inner)
+            | ExprKind::AnonParam(inner)
+            | ExprKind::Some(inner)
+            | ExprKind::Ok(inner)
+            | ExprKind::Err(inner)
+            | ExprKind::Break(Some(inner))
+            | ExprKind::Closure { body: inner, .. }
+            | ExprKind::Assign { value: inner, .. } => vec![inner],
+            ExprKind::List(items)
+            | ExprKind::Tuple(items)
+            | ExprKind::Set(items)
+            | ExprKind::Loop(items)
+            | ExprKind::Call { args: items, .. }
+            | ExprKind::Variant { args: items, .. } => items.iter_mut().collect(),
+            ExprKind::Map(entries) => entries.iter_mut().flat_map(|(k, v)| [k, v]).collect(),
+            ExprKind::Entry { map: lhs, key: rhs } | ExprKind::Binary { lhs, rhs, .. } => {
+                vec![lhs, rhs]
+            }
+            ExprKind::Ternary {
+                condition,
+                if_true,
+                if_false,
+            } => vec![condition, if_true, if_false],
+            ExprKind::For {
+                iterable: first,
+                body,
+                ..
+            }
+            | ExprKind::While {
+                condition: first,
+                body,
+            } => std::iter::once(&mut **first).chain(body).collect(),
+            ExprKind::Match { subject, arms } => std::iter::once(&mut **subject)
+                .chain(
+                    arms.iter_mut()
+                        .flat_map(|arm| arm.guard.iter_mut().chain(&mut arm.body)),
+                )
+                .collect(),
+        }
+    }
+
+    /// Splits `<map>{<key>}`, including `.<map>{<key>}` field access, into its map and key
+    fn as_entry(&self) -> Option<(Expr, &Expr)> {
+        match &self.kind {
+            ExprKind::Entry { map, key } => Some((*map.clone(), key)),
+            ExprKind::Binary { op, lhs, rhs } if op == "." => {
+                let (map, key) = rhs.as_entry()?;
+                Some((
+                    Expr::new(
+                        ExprKind::Binary {
+                            op: op.clone(),
+                            lhs: lhs.clone(),
+                            rhs: Box::new(map),
+                        },
+                        self.span.clone(),
+                    ),
+                    key,
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// Same as [`ToRust::to_rust`] but parenthesized if this is a binary
+    /// expression whose operator precedence needs grouping
+    fn to_rust_operand(&self, needs_parens: impl Fn(u8) -> bool) -> String {
+        match &self.kind {
+            ExprKind::Binary { op, .. } if !is_assignment(op) && needs_parens(precedence(op)) => {
+                format!("({})", self.to_rust())
             }
-            Expr::Closure { body, .. } => body.to_rust_type(),
+            _ => self.to_rust(),
         }
     }
+
+    /// Whether this is known to be a `HashSet`, judging by its inferred type
+    fn is_set(&self) -> bool {
+        matches!(self.ty, Some(Ty::Set(_)))
+    }
+
+    /// Rust type of this expression, with `_` for what could not be inferred
+    fn to_rust_type(&self) -> String {
+        self.ty.as_ref().map_or(String::from("_"), Ty::to_rust)
+    }
 }
 
 impl ToRust for Expr {
     fn to_rust(&self) -> String {
-        match self {
-            Expr::This => String::from("self"),
-            Expr::None => String::from("None"),
-            Expr::Reference(inner) => format!("&{}", inner.to_rust()),
-            Expr::List(items) => format!("vec![{}]", items.to_rust(",")),
-            Expr::Literal(lit) => lit.to_rust(),
-            Expr::Variable(name) => name.to_rust(),
-            Expr::AnonParam(param) => param.to_rust(),
-            Expr::Some(expr) => format!("Some({})", expr.to_rust()),
-            Expr::Ok(expr) => format!("Ok({})", expr.to_rust()),
-            Expr::Err(expr) => format!("Err({})", expr.to_rust()),
-            Expr::Binary { op, lhs, rhs } => match op.as_str() {
+        let rust = match &self.kind {
+            ExprKind::This => String::from("self"),
+            ExprKind::None => String::from("None"),
+            ExprKind::Reference(inner) => format!("&{}", inner.to_rust()),
+            ExprKind::List(items) => format!("vec![{}]", items.to_rust(",")),
+            ExprKind::Tuple(items) => format!("({})", items.to_rust(",")),
+            ExprKind::Map(entries) if entries.is_empty() => String::from("HashMap::new()"),
+            ExprKind::Map(entries) => format!(
+                "HashMap::from([{}])",
+                entries
+                    .iter()
+                    .map(|(key, value)| format!("({},{})", key.to_rust(), value.to_rust()))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+            ExprKind::Set(items) if items.is_empty() => String::from("HashSet::new()"),
+            ExprKind::Set(items) => format!("HashSet::from([{}])", items.to_rust(",")),
+            ExprKind::Entry { map, key } => format!("{}[&{}]", map.to_rust(), key.to_rust()),
+            ExprKind::Literal(lit) => lit.to_rust(),
+            // string constants are `&str` in Rust
+            ExprKind::Variable(name) if is_const_name(name) && self.ty == Some(Ty::String) => {
+                format!("String::from({})", name.to_rust())
+            }
+            ExprKind::Variable(name) => name.to_rust(),
+            ExprKind::AnonParam(param) => param.to_rust(),
+            ExprKind::Some(expr) => format!("Some({})", expr.to_rust()),
+            ExprKind::Ok(expr) => format!("Ok({})", expr.to_rust()),
+            ExprKind::Err(expr) => format!("Err({})", expr.to_rust()),
+            ExprKind::Binary { op, lhs, rhs } => match op.as_str() {
+                "<<" if matches!(lhs.ty, Some(Ty::List(_))) => {
+                    format!("{}.push({})", lhs.to_rust(), rhs.to_rust())
+                }
                 "<<" | "<|" => {
                     let write_func = if op == "<<" { "" } else { "ln" };
-                    if let Expr::Binary {
+                    if let ExprKind::Binary {
                         lhs: inner_lhs,
                         rhs: inner_rhs,
                         op,
-                    } = &**rhs
+                    } = &rhs.kind
+                        && matches!(op.as_str(), "<<" | "<|")
                     {
-                        let rhs = Expr::Binary {
-                            lhs: lhs.clone(),
-                            op: op.clone(),
-                            rhs: inner_rhs.clone(),
-                        };
+                        let rhs = Expr::new(
+                            ExprKind::Binary {
+                                lhs: lhs.clone(),
+                                op: op.clone(),
+                                rhs: inner_rhs.clone(),
+                            },
+                            rhs.span.clone(),
+                        );
                         format!(
                             "write{}!(stdout(), \"{{}}\", {}).unwrap();{}",
                             write_func,
@@ -216,9 +520,38 @@ impl ToRust for Expr {
                         )
                     }
                 }
-                _ => format!("{}{}{}", lhs.to_rust(), op, rhs.to_rust()),
+                "&" | "|" | "^" if lhs.is_set() && rhs.is_set() => {
+                    format!("&({}) {} &({})", lhs.to_rust(), op, rhs.to_rust())
+                }
+                "=" if let Some((map, key)) = lhs.as_entry() => format!(
+                    "{}.insert({},{})",
+                    map.to_rust(),
+                    key.to_rust(),
+                    rhs.to_rust()
+                ),
+                _ if is_assignment(op)
+                    && let Some((map, key)) = lhs.as_entry() =>
+                {
+                    format!(
+                        "*{}.entry({}).or_default(){}{}",
+                        map.to_rust(),
+                        key.to_rust(),
+                        op,
+                        rhs.to_rust()
+                    )
+                }
+                _ => {
+                    let prec = precedence(op);
+                    let is_comparison = prec == Token::Equal.get_precedence();
+                    format!(
+                        "{}{}{}",
+                        lhs.to_rust_operand(|p| p < prec || (is_comparison && p == prec)),
+                        op,
+                        rhs.to_rust_operand(|p| p <= prec)
+                    )
+                }
             },
-            Expr::Ternary {
+            ExprKind::Ternary {
                 condition,
                 if_true,
                 if_false,
@@ -228,46 +561,133 @@ impl ToRust for Expr {
                 if_true.to_rust(),
                 if_false.to_rust()
             ),
-            Expr::Call { callee, args } => format!(
+            ExprKind::Call { callee, args } => format!(
                 "{}({}{})",
                 if callee == "main" { "start" } else { &callee },
                 if callee == "join" { "&" } else { "" },
                 args.to_rust(",")
             ),
-            Expr::Assign {
-                name,
+            ExprKind::Variant {
+                enum_name,
+                variant,
+                args,
+            } => {
+                if args.is_empty() {
+                    format!("{}::{}", enum_name.to_rust(), variant.to_rust())
+                } else {
+                    format!(
+                        "{}::{}({})",
+                        enum_name.to_rust(),
+                        variant.to_rust(),
+                        args.to_rust(",")
+                    )
+                }
+            }
+            ExprKind::Assign {
+                pattern,
                 mutable,
                 assign_type,
                 value,
                 type_hint,
             } => {
-                let (t, val) = if let Some(ty) = type_hint.as_ref() {
-                    (ty.to_rust(), value.to_rust())
-                } else if matches!(assign_type, AssignType::Const | AssignType::Static)
-                    && let Expr::Literal(Literal::String(val)) = *value.clone()
+                let (t, val) = if matches!(assign_type, AssignType::Const | AssignType::Static)
+                    && let ExprKind::Literal(Literal::String(val)) = &value.kind
                 {
                     (String::from("&str"), format!("\"{}\"", val))
+                } else if let Some(ty) = type_hint.as_ref() {
+                    (ty.to_rust(), value.to_rust())
                 } else {
                     (value.to_rust_type(), value.to_rust())
                 };
                 format!(
-                    "{} {}{}:{}={};",
+                    "{} {}:{}={};",
                     assign_type.to_rust(),
-                    if *mutable { "mut " } else { "" },
-                    name,
+                    if *mutable {
+                        pattern.to_rust_mutable()
+                    } else {
+                        pattern.to_rust()
+                    },
                     t,
                     val
                 )
             }
-            Expr::Closure { args, body } => {
+            ExprKind::Closure { args, body } => {
                 format!("|{}|{}", args.to_rust(","), body.to_rust())
             }
+            ExprKind::For {
+                pattern,
+                iterable,
+                body,
+            } => format!(
+                "for {} in {} {{{};}}",
+                pattern.to_rust(),
+                iterable.to_rust(),
+                body.to_rust(";")
+            ),
+            ExprKind::While { condition, body } => {
+                format!("while {} {{{};}}", condition.to_rust(), body.to_rust(";"))
+            }
+            ExprKind::Loop(body) => format!("loop {{{};}}", body.to_rust(";")),
+            ExprKind::Match { subject, arms } => format!(
+                "match {}{} {{{}}}",
+                deref_for(arms.iter().map(|arm| &arm.pattern)),
+                subject.to_rust_operand(|_| true),
+                arms.to_rust(",")
+            ),
+            ExprKind::Break(value) => match value {
+                Some(value) => format!("break {}", value.to_rust()),
+                None => String::from("break"),
+            },
+            ExprKind::Continue => String::from("continue"),
+        };
+        mark(&self.span, rust)
+    }
+}
+
+#[derive(Clone)]
+pub struct Type {
+    pub kind: TypeKind,
+    pub span: Span,
+}
+
+impl Type {
+    pub fn new(kind: TypeKind, span: Span) -> Self {
+        Type { kind, span }
+    }
+
+    /// Adds the names of the generic types used in this type to `names`, in order of appearance
+    pub fn generics(&self, names: &mut Vec<String>) {
+        match &self.kind {
+            TypeKind::Generic(name) => {
+                if !names.contains(name) {
+                    names.push(name.clone());
+                }
+            }
+            TypeKind::Reference(inner)
+            | TypeKind::List(inner)
+            | TypeKind::Array(inner, _)
+            | TypeKind::Option(inner)
+            | TypeKind::Set(inner) => inner.generics(names),
+            TypeKind::Result(a, b) | TypeKind::Map(a, b) => {
+                a.generics(names);
+                b.generics(names);
+            }
+            TypeKind::Named(_, types) | TypeKind::Tuple(types) => {
+                types.iter().for_each(|t| t.generics(names))
+            }
+            TypeKind::Closure(params, returns) => {
+                params.iter().chain(returns).for_each(|t| t.generics(names))
+            }
+            TypeKind::Unsigned(_)
+            | TypeKind::Integer(_)
+            | TypeKind::Float(_)
+            | TypeKind::String => (),
         }
     }
 }
 
 #[derive(Clone)]
-pub enum Type {
+pub enum TypeKind {
     Unsigned(u8),
     Integer(u8),
     Float(u8),
@@ -278,23 +698,39 @@ pub enum Type {
     Option(Box<Type>),
     Result(Box<Type>, Box<Type>),
     Generic(String),
+    /// A struct or enum with its generic type arguments
+    Named(String, Vec<Type>),
+    Tuple(Vec<Type>),
+    Map(Box<Type>, Box<Type>),
+    Set(Box<Type>),
     Closure(Vec<Type>, Vec<Type>),
 }
 
 impl ToRust for Type {
     fn to_rust(&self) -> String {
-        match self {
-            Type::Unsigned(bits) => format!("u{bits}"),
-            Type::Integer(bits) => format!("i{bits}"),
-            Type::Float(bits) => format!("f{bits}"),
-            Type::String => String::from("String"),
-            Type::Reference(inner) => format!("&{}", inner.to_rust()),
-            Type::List(inner) => format!("Vec<{}>", inner.to_rust()),
-            Type::Array(inner, size) => format!("[{};{}]", inner.to_rust(), size),
-            Type::Option(inner) => format!("Option<{}>", inner.to_rust()),
-            Type::Result(inner, err) => format!("Result<{},{}>", inner.to_rust(), err.to_rust()),
-            Type::Generic(name) => name.to_rust(),
-            Type::Closure(arg_types, return_type) => {
+        match &self.kind {
+            TypeKind::Unsigned(bits) => format!("u{bits}"),
+            TypeKind::Integer(bits) => format!("i{bits}"),
+            TypeKind::Float(bits) => format!("f{bits}"),
+            TypeKind::String => String::from("String"),
+            TypeKind::Reference(inner) => format!("&{}", inner.to_rust()),
+            TypeKind::List(inner) => format!("Vec<{}>", inner.to_rust()),
+            TypeKind::Array(inner, size) => format!("[{};{}]", inner.to_rust(), size),
+            TypeKind::Option(inner) => format!("Option<{}>", inner.to_rust()),
+            TypeKind::Result(inner, err) => {
+                format!("Result<{},{}>", inner.to_rust(), err.to_rust())
+            }
+            TypeKind::Generic(name) => name.to_rust(),
+            TypeKind::Named(name, args) if args.is_empty() => name.to_rust(),
+            TypeKind::Named(name, args) => format!("{}<{}>", name.to_rust(), args.to_rust(",")),
+            TypeKind::Tuple(types) => format!("({})", types.to_rust(",")),
+            TypeKind::Set(inner) => format!("std::collections::HashSet<{}>", inner.to_rust()),
+            TypeKind::Map(key, value) => format!(
+                "std::collections::HashMap<{},{}>",
+                key.to_rust(),
+                value.to_rust()
+            ),
+            TypeKind::Closure(arg_types, return_type) => {
                 format!(
                     "impl Fn({})->({})",
                     arg_types.to_rust(","),
@@ -305,25 +741,56 @@ impl ToRust for Type {
     }
 }
 
+/// `<generic>: <trait> + <trait>` in the `<...>` list after the name of a type or function
+#[derive(Clone)]
+pub struct Bound {
+    pub generic: String,
+    pub traits: Vec<Type>,
+    pub span: Span,
+}
+
+impl ToRust for Bound {
+    fn to_rust(&self) -> String {
+        let rust = format!("{}: {}", self.generic.to_rust(), self.traits.to_rust("+"));
+        mark(&self.span, rust)
+    }
+}
+
+/// A `where` clause with `bounds`, or nothing if there are none
+fn where_clause(bounds: &[Bound]) -> String {
+    if bounds.is_empty() {
+        String::new()
+    } else {
+        let bounds = bounds.iter().map(Bound::to_rust).collect::<Vec<_>>();
+        format!(" where {}", bounds.join(","))
+    }
+}
+
 pub struct TypeSignature {
     pub name: String,
     pub param_names: Vec<String>,
     pub param_types: Vec<Type>,
     pub generics: Vec<String>,
+    pub bounds: Vec<Bound>,
     pub return_types: Vec<Type>,
     pub is_method: bool,
+    pub span: Span,
 }
 
 impl ToRust for TypeSignature {
     fn to_rust(&self) -> String {
-        format!(
-            "fn {}{}({}{}) -> {}",
+        let rust = format!(
+            "fn {}{}({}{}) -> {}{}",
             if self.name == "main" {
                 String::from("start")
             } else {
                 self.name.to_rust()
             },
-            format!("<{}>", self.generics.to_rust(",")),
+            if self.generics.is_empty() {
+                String::new()
+            } else {
+                format!("<{}>", self.generics.to_rust(","))
+            },
             if self.is_method { "&mut self," } else { "" },
             self.param_names
                 .iter()
@@ -335,37 +802,102 @@ impl ToRust for TypeSignature {
                 0 => String::from("()"),
                 1 => self.return_types.to_rust(","),
                 _ => format!("({})", self.return_types.to_rust(",")),
-            }
-        )
+            },
+            where_clause(&self.bounds)
+        );
+        mark(&self.span, rust)
     }
 }
 
+/// `<name> <patterns> [<- <guard>] = <body>`
 pub struct Equation {
     pub parameters_list: Vec<Pattern>,
     pub guard: Option<Expr>,
     pub body: Vec<Expr>,
+    pub span: Span,
+}
+
+impl ToRust for Equation {
+    fn to_rust(&self) -> String {
+        let patterns = if self.parameters_list.len() == 1 {
+            self.parameters_list[0].to_rust()
+        } else {
+            format!("({})", self.parameters_list.to_rust(","))
+        };
+        let rust = format!(
+            "{}{} => {{{}}}",
+            patterns,
+            self.guard
+                .as_ref()
+                .map_or(String::new(), |guard| format!(" if {}", guard.to_rust())),
+            self.body.to_rust(";")
+        );
+        mark(&self.span, rust)
+    }
 }
 
 pub struct Function {
     pub signature: TypeSignature,
     pub body: Vec<Expr>,
+    /// Clauses that replace the body with a `match` over the parameters
+    pub equations: Vec<Equation>,
+    pub span: Span,
+}
+
+impl Function {
+    /// Matches the parameters against every equation in order
+    fn equations_to_rust(&self) -> String {
+        let params = self
+            .signature
+            .param_names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let patterns = self.equations.iter().map(|eq| &eq.parameters_list[i]);
+                format!("{}{}", deref_for(patterns), name.to_rust())
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "match {} {{{}}}",
+            if params.len() == 1 {
+                params[0].clone()
+            } else {
+                format!("({})", params.join(","))
+            },
+            self.equations.to_rust(",")
+        )
+    }
 }
 
 impl ToRust for Function {
     fn to_rust(&self) -> String {
-        format!(
-            "{} {{{}}}",
+        let rust = format!(
+            "{} {{{}{}}}",
             self.signature.to_rust(),
-            self.body.to_rust(";") + ";"
-        )
+            if self.equations.is_empty() {
+                self.body.to_rust(";")
+            } else {
+                self.equations_to_rust()
+            },
+            if self.signature.return_types.is_empty() {
+                ";"
+            } else {
+                ""
+            }
+        );
+        mark(&self.span, rust)
     }
 }
 
 pub struct Impl {
     pub struct_name: String,
     pub trait_name: String,
+    /// Types the generic types of the trait stand for, found by [`crate::traits::check`]
+    pub trait_types: Vec<Ty>,
     pub generic_types: Vec<String>,
+    pub bounds: Vec<Bound>,
     pub methods: Vec<Function>,
+    pub span: Span,
 }
 
 impl ToRust for Impl {
@@ -376,81 +908,189 @@ impl ToRust for Impl {
         } else {
             format!("<{}>", self.generic_types.to_rust(","))
         };
-        format!(
-            "impl{} {} for {}{} {{{}}}",
+        let trait_types = if self.trait_types.is_empty() {
+            String::new()
+        } else {
+            let types = self.trait_types.iter().map(Ty::to_rust);
+            format!("<{}>", types.collect::<Vec<_>>().join(","))
+        };
+        let rust = format!(
+            "impl{} {}{} for {}{}{} {{{}}}",
             generic_types,
             self.trait_name.to_rust(),
+            trait_types,
             self.struct_name.to_rust(),
             generic_types,
+            where_clause(&self.bounds),
             methods
-        )
+        );
+        mark(&self.span, rust)
     }
 }
 
-type Field = (String, Type);
+/// `<name>: <type>`, or `<name>; <type>` for a field that can be assigned to
+#[derive(Clone)]
+pub struct Field {
+    pub name: String,
+    pub r#type: Type,
+    pub mutable: bool,
+    pub span: Span,
+}
+
+impl ToRust for Field {
+    fn to_rust(&self) -> String {
+        format!("{}: {}", self.name.to_rust(), self.r#type.to_rust())
+    }
+}
 
 pub struct Struct {
     pub name: String,
     pub generics: Vec<String>,
+    pub bounds: Vec<Bound>,
     pub fields: Vec<Field>,
     pub methods: Vec<Function>,
+    /// Names of the methods defined by the impls of this struct
+    pub impl_methods: Vec<String>,
+    pub span: Span,
+}
+
+impl Struct {
+    /// A getter for every field and a setter for every mutable one,
+    /// unless the struct or one of its impls already has a method of that name
+    fn accessors_to_rust(&self) -> String {
+        let is_free = |name: &str| {
+            !self.methods.iter().any(|m| m.signature.name == name)
+                && !self.impl_methods.iter().any(|m| m == name)
+        };
+        let mut out = String::new();
+        for field in &self.fields {
+            let name = field.name.to_rust();
+            let field_type = field.r#type.to_rust();
+            if is_free(&field.name) {
+                out += &format!("pub fn {name}(&self) -> &{field_type} {{&self.{name}}}");
+            }
+            let mut chars = field.name.chars();
+            let setter = match chars.next() {
+                Some(first) => format!("set{}{}", first.to_uppercase(), chars.as_str()),
+                None => String::from("set"),
+            };
+            if field.mutable && is_free(&setter) {
+                out += &format!(
+                    "pub fn {setter}(&mut self, {name}: {field_type}) {{self.{name} = {name};}}"
+                );
+            }
+        }
+        out
+    }
 }
 
 impl ToRust for Struct {
     fn to_rust(&self) -> String {
-        let fields = self
-            .fields
-            .iter()
-            .map(|(name, kind)| format!("{}: {}", name.to_rust(), kind.to_rust()))
-            .collect::<Vec<_>>()
-            .join(", ");
+        let fields = self.fields.to_rust(", ");
         let name = self.name.to_rust();
         let generics = if self.generics.is_empty() {
             String::new()
         } else {
             format!("<{}>", self.generics.to_rust(","))
         };
-        let base = format!("struct {}{} {{{}}}", name, generics, fields);
-        if self.methods.is_empty() {
-            return base;
+        let where_clause = where_clause(&self.bounds);
+        let base = format!("struct {}{}{} {{{}}}", name, generics, where_clause, fields);
+        if self.fields.is_empty() && self.methods.is_empty() {
+            return mark(&self.span, base);
         }
-        format!(
-            "{} impl{} {}{} {{{}}}",
+        let rust = format!(
+            "{} impl{} {}{}{} {{{}{}}}",
             base,
             generics,
             name,
             generics,
+            where_clause,
+            self.accessors_to_rust(),
             self.methods.to_rust("")
-        )
+        );
+        mark(&self.span, rust)
+    }
+}
+
+pub enum Variant {
+    Unit(String),
+    Tuple(String, Vec<Type>),
+    Struct(String, Vec<Field>),
+}
+
+impl ToRust for Variant {
+    fn to_rust(&self) -> String {
+        match self {
+            Variant::Unit(name) => name.to_rust(),
+            Variant::Tuple(name, types) => format!("{}({})", name.to_rust(), types.to_rust(",")),
+            Variant::Struct(name, fields) => {
+                format!("{} {{{}}}", name.to_rust(), fields.to_rust(", "))
+            }
+        }
+    }
+}
+
+pub struct Enum {
+    pub name: String,
+    pub generics: Vec<String>,
+    pub bounds: Vec<Bound>,
+    pub variants: Vec<Variant>,
+    pub span: Span,
+}
+
+impl ToRust for Enum {
+    fn to_rust(&self) -> String {
+        let generics = if self.generics.is_empty() {
+            String::new()
+        } else {
+            format!("<{}>", self.generics.to_rust(","))
+        };
+        let rust = format!(
+            "enum {}{}{} {{{}}}",
+            self.name.to_rust(),
+            generics,
+            where_clause(&self.bounds),
+            self.variants.to_rust(",")
+        );
+        mark(&self.span, rust)
     }
 }
 
 pub struct Trait {
     pub name: String,
     pub generics: Vec<String>,
+    pub bounds: Vec<Bound>,
     pub signatures: Vec<TypeSignature>,
+    pub span: Span,
 }
 
 impl ToRust for Trait {
     fn to_rust(&self) -> String {
-        let signatures = self.signatures.to_rust(";");
+        let signatures = self
+            .signatures
+            .iter()
+            .map(|s| format!("{};", s.to_rust()))
+            .collect::<String>();
         let generics = if self.generics.is_empty() {
             String::new()
         } else {
             format!("<{}>", self.generics.to_rust(","))
         };
-        format!(
-            "trait {}{} {{{}}}",
+        let rust = format!(
+            "trait {}{}{} {{{}}}",
             self.name.to_rust(),
             generics,
+            where_clause(&self.bounds),
             signatures
-        )
+        );
+        mark(&self.span, rust)
     }
 }
 
 pub struct Import {
     pub filename: String,
     pub items: Vec<String>,
+    pub span: Span,
 }
 
 impl ToRust for Import {
@@ -463,6 +1103,10 @@ impl ToRust for Import {
                 for item in &self.items {
                     match item.as_str() {
                         "cout" => import.push_str("io::{Write, stdout},"),
+                        "HashMap" => import.push_str("collections::HashMap,"),
+                        "HashSet" => import.push_str("collections::HashSet,"),
+                        "Display" => import.push_str("fmt::Display,"),
+                        "Debug" => import.push_str("fmt::Debug,"),
                         _ => (),
                     }
                 }
@@ -480,9 +1124,11 @@ impl ToRust for Import {
     }
 }
 
+#[derive(Default)]
 pub struct Program {
     pub imports: Vec<Import>,
     pub traits: Vec<Trait>,
+    pub enums: Vec<Enum>,
     pub structs: Vec<Struct>,
     pub impls: Vec<Impl>,
     pub functions: Vec<Function>,
@@ -497,6 +1143,7 @@ impl ToRust for Program {
             .iter()
             .map(|i| i as &dyn ToRust)
             .chain(self.traits.iter().map(|t| t as &dyn ToRust))
+            .chain(self.enums.iter().map(|e| e as &dyn ToRust))
             .chain(self.structs.iter().map(|s| s as &dyn ToRust))
             .chain(self.impls.iter().map(|i| i as &dyn ToRust))
             .chain(self.variables.iter().map(|v| v as &dyn ToRust))
@@ -0,0 +1,382 @@
+use std::{collections::HashMap, fmt::Display, mem::discriminant};
+
+use crate::{
+    ast::{AssignType, Expr, ExprKind, Literal, PatternKind, Program},
+    diagnostic::Diagnostic,
+    span::Span,
+};
+
+/// Evaluates the value of every `CONST :=` declaration at compile time and replaces
+/// it with the resulting literal, so the generated Rust `const` needs no runtime code
+///
+/// It runs before [`crate::typeck::check`], so constants are typed from their values
+/// no matter the order they are declared in.
+pub fn evaluate(program: &mut Program) -> Vec<Diagnostic> {
+    let constants = program
+        .variables
+        .iter()
+        .filter_map(|variable| match &variable.kind {
+            ExprKind::Assign {
+                pattern,
+                assign_type: AssignType::Const,
+                value,
+                ..
+            } => match &pattern.kind {
+                PatternKind::Variable(name) => Some((name.clone(), &**value)),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+    let mut evaluator = Evaluator {
+        constants: &constants,
+        values: HashMap::new(),
+        evaluating: vec![],
+        diagnostics: vec![],
+    };
+    for variable in &program.variables {
+        if let ExprKind::Assign { pattern, .. } = &variable.kind
+            && let PatternKind::Variable(name) = &pattern.kind
+            && constants.contains_key(name)
+        {
+            evaluator.constant(name, &pattern.span);
+        }
+    }
+    let (mut values, diagnostics) = (evaluator.values, evaluator.diagnostics);
+    for variable in &mut program.variables {
+        if let ExprKind::Assign { pattern, value, .. } = &mut variable.kind
+            && let PatternKind::Variable(name) = &pattern.kind
+            && let Some(Some(result)) = values.remove(name)
+        {
+            value.kind = ExprKind::Literal(result.into_literal());
+        }
+    }
+    diagnostics
+}
+
+#[derive(Clone, PartialEq, PartialOrd)]
+enum Value {
+    Integer(i128),
+    /// An integer above `i128::MAX`, or one used along with such an integer
+    Unsigned(u128),
+    Float(f64),
+    String(String),
+    Bool(bool),
+}
+
+impl Value {
+    fn into_literal(self) -> Literal {
+        match self {
+            Value::Integer(i) => Literal::Integer(i),
+            Value::Unsigned(u) => i128::try_from(u).map_or(Literal::Unsigned(u), Literal::Integer),
+            Value::Float(f) => Literal::Float(f),
+            Value::String(s) => Literal::String(s),
+            Value::Bool(b) => Literal::Bool(b),
+        }
+    }
+
+    /// The Soulite type of this value
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::Integer(_) => "Z64",
+            Value::Unsigned(_) => "N128",
+            Value::Float(_) => "R64",
+            Value::String(_) => "String",
+            Value::Bool(_) => "Bool",
+        }
+    }
+
+    /// The text of this value inside an interpolated string
+    fn display(&self) -> String {
+        match self {
+            Value::Integer(i) => i.to_string(),
+            Value::Unsigned(u) => u.to_string(),
+            Value::Float(f) => f.to_string(),
+            Value::String(s) => s.clone(),
+            Value::Bool(b) => b.to_string(),
+        }
+    }
+}
+
+struct Evaluator<'a> {
+    /// Initializer of each constant by its name
+    constants: &'a HashMap<String, &'a Expr>,
+    /// Value of each constant evaluated so far, or `None` if it could not be evaluated
+    values: HashMap<String, Option<Value>>,
+    /// Names of the constants being evaluated, innermost last, to find cycles
+    evaluating: Vec<String>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Evaluator<'_> {
+    /// The value of the constant `name`, referred to at `span`
+    fn constant(&mut self, name: &str, span: &Span) -> Option<Value> {
+        if let Some(value) = self.values.get(name) {
+            return value.clone();
+        }
+        if let Some(start) = self.evaluating.iter().position(|n| n == name) {
+            let cycle = self.evaluating[start..].join("` -> `");
+            self.diagnostics.push(
+                Diagnostic::error(format!(
+                    "cycle detected when evaluating constant `{}`",
+                    name
+                ))
+                .with_code("SL0025")
+                .with_primary(span.clone(), format!("`{}` depends on itself", name))
+                .with_note(format!("the cycle is `{}` -> `{}`", cycle, name)),
+            );
+            return None;
+        }
+        self.evaluating.push(name.to_string());
+        let value = self.eval(self.constants[name]);
+        self.evaluating.pop();
+        self.values.insert(name.to_string(), value.clone());
+        value
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Option<Value> {
+        match &expr.kind {
+            ExprKind::Literal(Literal::Integer(i)) => Some(Value::Integer(*i)),
+            ExprKind::Literal(Literal::Unsigned(u)) => Some(Value::Unsigned(*u)),
+            ExprKind::Literal(Literal::Float(f)) => Some(Value::Float(*f)),
+            ExprKind::Literal(Literal::String(s)) => self.interpolate(s, &expr.span),
+            ExprKind::Literal(Literal::Bool(b)) => Some(Value::Bool(*b)),
+            ExprKind::Variable(name) if self.constants.contains_key(name) => {
+                self.constant(name, &expr.span)
+            }
+            ExprKind::Variable(name) => {
+                self.diagnostics.push(
+                    Diagnostic::error(format!("`{}` is not a constant", name))
+                        .with_code("SL0025")
+                        .with_primary(expr.span.clone(), "not a constant")
+                        .with_note("constants can only refer to other constants"),
+                );
+                None
+            }
+            ExprKind::Binary { op, lhs, rhs } => {
+                let (lhs, rhs) = (self.eval(lhs)?, self.eval(rhs)?);
+                self.binary(op, lhs, rhs, &expr.span)
+            }
+            ExprKind::Ternary {
+                condition,
+                if_true,
+                if_false,
+            } => match self.eval(condition)? {
+                Value::Bool(true) => self.eval(if_true),
+                Value::Bool(false) => self.eval(if_false),
+                _ => self.unsupported(&condition.span),
+            },
+            _ => self.unsupported(&expr.span),
+        }
+    }
+
+    fn unsupported(&mut self, span: &Span) -> Option<Value> {
+        self.diagnostics.push(
+            Diagnostic::error("cannot evaluate this expression at compile time")
+                .with_code("SL0025")
+                .with_primary(span.clone(), "not a constant expression")
+                .with_note(
+                    "constants can only use literals, other constants, arithmetic, \
+                     comparisons, string concatenation and `<-` `;` conditions",
+                ),
+        );
+        None
+    }
+
+    fn failed(&mut self, span: &Span, message: String) -> Option<Value> {
+        self.diagnostics.push(
+            Diagnostic::error("evaluation of constant value failed")
+                .with_code("SL0025")
+                .with_primary(span.clone(), message),
+        );
+        None
+    }
+
+    fn binary(&mut self, op: &str, lhs: Value, rhs: Value, span: &Span) -> Option<Value> {
+        let overflow = |a: &dyn Display, b: &dyn Display| {
+            format!("attempt to compute `{a} {op} {b}`, which would overflow")
+        };
+        // non-negative integers can be used along with integers above `i128::MAX`
+        let (lhs, rhs) = match (lhs, rhs) {
+            (Value::Integer(a), rhs @ Value::Unsigned(_)) if a >= 0 => {
+                (Value::Unsigned(a as u128), rhs)
+            }
+            (lhs @ Value::Unsigned(_), Value::Integer(b)) if b >= 0 => {
+                (lhs, Value::Unsigned(b as u128))
+            }
+            operands => operands,
+        };
+        let same_type = discriminant(&lhs) == discriminant(&rhs);
+        let result = match (op, &lhs, &rhs) {
+            ("==", _, _) if same_type => Value::Bool(lhs == rhs),
+            ("!=", _, _) if same_type => Value::Bool(lhs != rhs),
+            ("<", _, _) if same_type => Value::Bool(lhs < rhs),
+            ("<=", _, _) if same_type => Value::Bool(lhs <= rhs),
+            (">", _, _) if same_type => Value::Bool(lhs > rhs),
+            (">=", _, _) if same_type => Value::Bool(lhs >= rhs),
+            ("&&", Value::Bool(a), Value::Bool(b)) => Value::Bool(*a && *b),
+            ("||", Value::Bool(a), Value::Bool(b)) => Value::Bool(*a || *b),
+            ("+", Value::String(a), Value::String(b)) => Value::String(format!("{a}{b}")),
+            ("/" | "%", Value::Integer(a), Value::Integer(0)) => {
+                return self.failed(span, format!("attempt to divide `{a}` by zero"));
+            }
+            ("**", Value::Integer(a), Value::Integer(b)) if *b < 0 => {
+                return self.failed(
+                    span,
+                    format!("attempt to raise `{a}` to the negative power `{b}`"),
+                );
+            }
+            ("+" | "-" | "*" | "/" | "%" | "**", Value::Integer(a), Value::Integer(b)) => {
+                let result = match op {
+                    "+" => a.checked_add(*b),
+                    "-" => a.checked_sub(*b),
+                    "*" => a.checked_mul(*b),
+                    "/" => a.checked_div(*b),
+                    "%" => a.checked_rem(*b),
+                    _ => u32::try_from(*b).ok().and_then(|b| a.checked_pow(b)),
+                };
+                match result {
+                    Some(result) => Value::Integer(result),
+                    None => return self.failed(span, overflow(a, b)),
+                }
+            }
+            ("/" | "%", Value::Unsigned(a), Value::Unsigned(0)) => {
+                return self.failed(span, format!("attempt to divide `{a}` by zero"));
+            }
+            ("+" | "-" | "*" | "/" | "%" | "**", Value::Unsigned(a), Value::Unsigned(b)) => {
+                let result = match op {
+                    "+" => a.checked_add(*b),
+                    "-" => a.checked_sub(*b),
+                    "*" => a.checked_mul(*b),
+                    "/" => a.checked_div(*b),
+                    "%" => a.checked_rem(*b),
+                    _ => u32::try_from(*b).ok().and_then(|b| a.checked_pow(b)),
+                };
+                match result {
+                    Some(result) => Value::Unsigned(result),
+                    None => return self.failed(span, overflow(a, b)),
+                }
+            }
+            ("&", Value::Integer(a), Value::Integer(b)) => Value::Integer(a & b),
+            ("|", Value::Integer(a), Value::Integer(b)) => Value::Integer(a | b),
+            ("^", Value::Integer(a), Value::Integer(b)) => Value::Integer(a ^ b),
+            ("&", Value::Unsigned(a), Value::Unsigned(b)) => Value::Unsigned(a & b),
+            ("|", Value::Unsigned(a), Value::Unsigned(b)) => Value::Unsigned(a | b),
+            ("^", Value::Unsigned(a), Value::Unsigned(b)) => Value::Unsigned(a ^ b),
+            ("&", Value::Bool(a), Value::Bool(b)) => Value::Bool(a & b),
+            ("|", Value::Bool(a), Value::Bool(b)) => Value::Bool(a | b),
+            ("^", Value::Bool(a), Value::Bool(b)) => Value::Bool(a ^ b),
+            ("+", Value::Float(a), Value::Float(b)) => Value::Float(a + b),
+            ("-", Value::Float(a), Value::Float(b)) => Value::Float(a - b),
+            ("*", Value::Float(a), Value::Float(b)) => Value::Float(a * b),
+            ("/", Value::Float(a), Value::Float(b)) => Value::Float(a / b),
+            ("%", Value::Float(a), Value::Float(b)) => Value::Float(a % b),
+            ("**", Value::Float(a), Value::Float(b)) => Value::Float(a.powf(*b)),
+            _ => {
+                return self.failed(
+                    span,
+                    format!(
+                        "cannot apply `{}` to `{}` and `{}`",
+                        op,
+                        lhs.type_name(),
+                        rhs.type_name()
+                    ),
+                );
+            }
+        };
+        Some(result)
+    }
+
+    /// The value of a string literal, with each `{CONSTANT}` in it replaced by its value
+    fn interpolate(&mut self, literal: &str, span: &Span) -> Option<Value> {
+        let mut result = String::new();
+        let mut chars = literal.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    result.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    result.push('}');
+                }
+                '{' => {
+                    let name = chars.by_ref().take_while(|&c| c != '}').collect::<String>();
+                    let name = name.trim();
+                    if !self.constants.contains_key(name) {
+                        self.diagnostics.push(
+                            Diagnostic::error(format!(
+                                "cannot interpolate `{}` into a constant",
+                                name
+                            ))
+                            .with_code("SL0025")
+                            .with_primary(span.clone(), format!("`{}` is not a constant", name))
+                            .with_note("only other constants can be interpolated, as `{NAME}`"),
+                        );
+                        return None;
+                    }
+                    result += &self.constant(name, span)?.display();
+                }
+                c => result.push(c),
+            }
+        }
+        Some(Value::String(result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::{ast::ToRust, parser, span::SourceFile};
+
+    /// The Rust for the literal each constant in `source` evaluates to
+    fn evaluated(source: &str) -> Vec<String> {
+        let file = Rc::new(SourceFile::new("test.sl", source));
+        let (mut program, diagnostics) = parser::parse::<false>(file);
+        assert!(diagnostics.is_empty());
+        assert!(evaluate(&mut program).is_empty());
+        program
+            .variables
+            .iter()
+            .map(|variable| match &variable.kind {
+                ExprKind::Assign { value, .. } => match &value.kind {
+                    ExprKind::Literal(literal) => literal.to_rust(),
+                    _ => panic!("constant was not evaluated to a literal"),
+                },
+                _ => panic!("not a constant"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(
+            evaluated("A := 2 + 3 * 4\nB := (2 + 3) * 4\nC := 2 ** 10 % 1000\n"),
+            ["14", "20", "24"]
+        );
+    }
+
+    #[test]
+    fn forward_references() {
+        assert_eq!(evaluated("A := B * 2\nB := 21\n"), ["42", "21"]);
+    }
+
+    #[test]
+    fn conditions_and_strings() {
+        assert_eq!(
+            evaluated("MAX := 10 <- 1 < 2 ; 20\nNAME := \"soul\" + \"ite\"\nHI := \"hi {NAME}\"\n"),
+            ["10", "format!(\"soulite\")", "format!(\"hi soulite\")"]
+        );
+    }
+
+    #[test]
+    fn unsigned() {
+        assert_eq!(
+            evaluated("A := 340282366920938463463374607431768211455 - 1\nB := A / A\n"),
+            ["340282366920938463463374607431768211454", "1"]
+        );
+    }
+}
@@ -0,0 +1,343 @@
+use std::{
+    fmt::Write,
+    io::{IsTerminal, stderr},
+    rc::Rc,
+};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::span::{SourceFile, Span};
+
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const CYAN: &str = "\x1b[1;36m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// Number of columns a tab takes up in a rendered source line
+const TAB_WIDTH: usize = 4;
+
+/// Number of columns `c` takes up in a rendered source line
+fn char_width(c: char) -> usize {
+    if c == '\t' { TAB_WIDTH } else { 1 }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    fn name(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+
+    fn style(self) -> &'static str {
+        match self {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+        }
+    }
+}
+
+/// A location in the source with a message about its part in a [`Diagnostic`]
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Label {
+    #[serde(flatten)]
+    pub span: Span,
+    #[serde(rename = "label")]
+    pub message: String,
+}
+
+/// A fix for a [`Diagnostic`] that replaces the source at `span` with `replacement`
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Suggestion {
+    pub message: String,
+    #[serde(flatten)]
+    pub span: Span,
+    pub replacement: String,
+}
+
+/// An error or warning about a Soulite file, or about compiling it
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Identifies the kind of problem, such as `SL0001` or `SLW0001` for Soulite's own errors
+    /// and warnings, or `E0308` when reported by `rustc`
+    pub code: Option<Box<str>>,
+    pub message: Box<str>,
+    /// Where the problem is, if it is in the source, boxed to keep `Result<_, Diagnostic>` small
+    #[serde(skip)]
+    pub primary: Option<Box<Label>>,
+    /// Other places in the source related to the problem
+    #[serde(skip)]
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl Diagnostic {
+    fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity,
+            code: None,
+            message: message.into().into_boxed_str(),
+            primary: None,
+            secondary: vec![],
+            notes: vec![],
+            suggestions: vec![],
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Diagnostic::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Diagnostic::new(Severity::Warning, message)
+    }
+
+    pub fn with_code(mut self, code: impl Into<String>) -> Self {
+        self.code = Some(code.into().into_boxed_str());
+        self
+    }
+
+    pub fn with_primary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.primary = Some(Box::new(Label {
+            span,
+            message: message.into(),
+        }));
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.secondary.push(Label {
+            span,
+            message: message.into(),
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_suggestion(
+        mut self,
+        message: impl Into<String>,
+        span: Span,
+        replacement: impl Into<String>,
+    ) -> Self {
+        self.suggestions.push(Suggestion {
+            message: message.into(),
+            span,
+            replacement: replacement.into(),
+        });
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// The primary label followed by the secondary ones, paired with whether they are primary
+    fn labels(&self) -> impl Iterator<Item = (&Label, bool)> {
+        self.primary
+            .as_deref()
+            .into_iter()
+            .map(|label| (label, true))
+            .chain(self.secondary.iter().map(|label| (label, false)))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum MessageFormat {
+    /// Reports like `rustc`, with source snippets
+    #[default]
+    Human,
+    /// One JSON object per line for each diagnostic
+    Json,
+}
+
+/// A [`Diagnostic`] as printed in [`MessageFormat::Json`], with its primary
+/// and secondary labels together in `spans` and its uncolored human report
+#[derive(Serialize)]
+struct JsonDiagnostic<'a> {
+    #[serde(flatten)]
+    diagnostic: &'a Diagnostic,
+    spans: Vec<JsonLabel<'a>>,
+    rendered: String,
+}
+
+#[derive(Serialize)]
+struct JsonLabel<'a> {
+    #[serde(flatten)]
+    label: &'a Label,
+    is_primary: bool,
+}
+
+/// Prints [`Diagnostic`]s to stderr in a [`MessageFormat`]
+pub struct Emitter {
+    format: MessageFormat,
+    /// Whether human reports are colored, which is only when printed to a terminal
+    color: bool,
+    files: Vec<Rc<SourceFile>>,
+}
+
+impl Emitter {
+    pub fn new(format: MessageFormat) -> Self {
+        Emitter {
+            format,
+            color: stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+            files: vec![],
+        }
+    }
+
+    /// Makes the source of `file` available for the snippets of reports
+    pub fn add_file(&mut self, file: Rc<SourceFile>) {
+        self.files.push(file);
+    }
+
+    pub fn emit(&self, diagnostic: &Diagnostic) {
+        match self.format {
+            MessageFormat::Human => eprintln!("{}\n", self.render(diagnostic, self.color)),
+            MessageFormat::Json => eprintln!("{}", self.to_json(diagnostic)),
+        }
+    }
+
+    fn file(&self, span: &Span) -> Option<&SourceFile> {
+        self.files
+            .iter()
+            .find(|file| file.name == span.file)
+            .map(|file| &**file)
+    }
+
+    /// Renders `diagnostic` like `rustc` does:
+    ///
+    /// ```text
+    /// error[SL0001]: expected `:` after const name, found `=`
+    ///  --> main.sl:3:5
+    ///   |
+    /// 3 | MAX = 10
+    ///   |     ^ expected `:` after const name
+    ///   |
+    ///   = help: use `:=` to declare a constant: `MAX := 10`
+    /// ```
+    fn render(&self, diagnostic: &Diagnostic, color: bool) -> String {
+        let paint = |text: &str, style: &str| {
+            if color {
+                format!("{style}{text}{RESET}")
+            } else {
+                text.to_string()
+            }
+        };
+        let severity = diagnostic.severity;
+        let mut out = match &diagnostic.code {
+            Some(code) => paint(&format!("{}[{}]", severity.name(), code), severity.style()),
+            None => paint(severity.name(), severity.style()),
+        };
+        out += &paint(&format!(": {}", diagnostic.message), BOLD);
+        let mut labels = diagnostic.labels().collect::<Vec<_>>();
+        labels.sort_by_key(|(label, _)| label.span.range.start);
+        let width = labels
+            .iter()
+            .map(|(label, _)| label.span.line.to_string().len())
+            .max()
+            .unwrap_or(0);
+        let gutter = " ".repeat(width);
+        let bar = paint("|", BLUE);
+        if let Some((label, _)) = diagnostic.labels().next() {
+            write!(out, "\n{}{} {}", gutter, paint("-->", BLUE), label.span).unwrap();
+            write!(out, "\n{} {}", gutter, bar).unwrap();
+        }
+        for (label, is_primary) in labels {
+            let Some(file) = self.file(&label.span) else {
+                continue;
+            };
+            let line = file.line(label.span.line);
+            let before: usize = line
+                .chars()
+                .take(label.span.column - 1)
+                .map(char_width)
+                .sum();
+            let underlined: usize = file.source[label.span.range.clone()]
+                .lines()
+                .next()
+                .map_or(0, |text| text.chars().map(char_width).sum());
+            let (mark, style) = if is_primary {
+                ("^", severity.style())
+            } else {
+                ("-", BLUE)
+            };
+            write!(
+                out,
+                "\n{} {} {}\n{} {} {}{}",
+                paint(&format!("{:>width$}", label.span.line), BLUE),
+                bar,
+                line.replace('\t', &" ".repeat(TAB_WIDTH)),
+                gutter,
+                bar,
+                " ".repeat(before),
+                paint(
+                    &format!("{} {}", mark.repeat(underlined.max(1)), label.message),
+                    style
+                ),
+            )
+            .unwrap();
+        }
+        if diagnostic.primary.is_some()
+            && (!diagnostic.notes.is_empty() || !diagnostic.suggestions.is_empty())
+        {
+            write!(out, "\n{} {}", gutter, bar).unwrap();
+        }
+        for note in &diagnostic.notes {
+            write!(out, "\n{} = {}: {}", gutter, paint("note", BOLD), note).unwrap();
+        }
+        for suggestion in &diagnostic.suggestions {
+            write!(
+                out,
+                "\n{} = {}: {}",
+                gutter,
+                paint("help", CYAN),
+                suggestion.message
+            )
+            .unwrap();
+            if let Some(file) = self.file(&suggestion.span) {
+                let span = &suggestion.span;
+                let line_start = file.line_start(span.line);
+                let line = file.line(span.line);
+                let end = (span.range.end - line_start).min(line.len());
+                let fixed = format!(
+                    "{}{}{}",
+                    &line[..span.range.start - line_start],
+                    suggestion.replacement,
+                    &line[end..]
+                );
+                write!(out, ": `{}`", fixed.trim()).unwrap();
+            }
+        }
+        out
+    }
+
+    /// Serializes `diagnostic` as a single line of JSON, with its
+    /// uncolored human report as `rendered`
+    fn to_json(&self, diagnostic: &Diagnostic) -> String {
+        serde_json::to_string(&JsonDiagnostic {
+            diagnostic,
+            spans: diagnostic
+                .labels()
+                .map(|(label, is_primary)| JsonLabel { label, is_primary })
+                .collect(),
+            rendered: self.render(diagnostic, false),
+        })
+        .expect("diagnostics always serialize")
+    }
+}
@@ -0,0 +1,460 @@
+use std::collections::HashMap;
+
+use crate::{
+    ast::{Enum, Expr, ExprKind, Function, Literal, Pattern, PatternKind, Program, Variant},
+    diagnostic::Diagnostic,
+    span::Span,
+    typeck::Ty,
+};
+
+/// Checks that every `match` and every function defined by equations covers all values
+/// of its subject, and that each arm or equation matches something the ones before it do not
+///
+/// The types of the subjects come from [`crate::typeck::check`], so it runs after that.
+pub fn check(program: &Program) -> Vec<Diagnostic> {
+    let mut checker = Checker {
+        enums: program.enums.iter().map(|e| (e.name.as_str(), e)).collect(),
+        diagnostics: vec![],
+    };
+    for variable in &program.variables {
+        checker.expr(variable);
+    }
+    let methods = program
+        .structs
+        .iter()
+        .flat_map(|s| &s.methods)
+        .chain(program.impls.iter().flat_map(|i| &i.methods));
+    for function in methods.chain(&program.functions) {
+        checker.function(function);
+    }
+    checker.diagnostics
+}
+
+/// A kind of value that patterns can tell apart from the others
+#[derive(Clone, PartialEq)]
+enum Constructor {
+    Integer(i128),
+    /// An integer above `i128::MAX`
+    Unsigned(u128),
+    Float(f64),
+    String(String),
+    Bool(bool),
+    /// A variant of an enum, by the names of the enum and the variant
+    Variant(String, String),
+    /// A tuple of this many elements
+    Tuple(usize),
+    /// A list of exactly this many elements
+    List(usize),
+}
+
+/// A [`Pattern`] with every variable replaced by a wildcard
+#[derive(Clone)]
+enum Pat {
+    Wildcard,
+    Constructor(Constructor, Vec<Pat>),
+}
+
+/// The patterns of a match arm, or of the parameters of an equation
+type Row = Vec<Pat>;
+
+/// An arm or equation to check: its patterns, their span, and whether it has a guard
+type Clause<'a> = (&'a [Pattern], Span, bool);
+
+struct Checker<'a> {
+    enums: HashMap<&'a str, &'a Enum>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker<'_> {
+    fn function(&mut self, function: &Function) {
+        for expr in &function.body {
+            self.expr(expr);
+        }
+        for equation in &function.equations {
+            for expr in equation.guard.iter().chain(&equation.body) {
+                self.expr(expr);
+            }
+        }
+        if function.equations.is_empty() {
+            return;
+        }
+        let signature = &function.signature;
+        let types = signature
+            .param_types
+            .iter()
+            .map(|t| Some(Ty::from_type(t)))
+            .collect::<Vec<_>>();
+        let clauses = function.equations.iter().map(|equation| {
+            let patterns = &equation.parameters_list;
+            let span = match (patterns.first(), patterns.last()) {
+                (Some(first), Some(last)) => first.span.to(&last.span),
+                _ => equation.span.clone(),
+            };
+            (patterns.as_slice(), span, equation.guard.is_some())
+        });
+        if let Some(missing) = self.clauses(clauses, &types) {
+            let case = format!("{} {}", signature.name, self.show_all(&missing));
+            self.diagnostics.push(
+                Diagnostic::error(format!("non-exhaustive equations: `{}` not covered", case))
+                    .with_code("SL0024")
+                    .with_primary(signature.span.clone(), format!("`{}` not covered", case))
+                    .with_note(format!(
+                        "add an equation that matches it, such as `{} = ...`",
+                        case
+                    )),
+            );
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        if let ExprKind::Match { subject, arms } = &expr.kind {
+            let clauses = arms.iter().map(|arm| {
+                (
+                    std::slice::from_ref(&arm.pattern),
+                    arm.pattern.span.clone(),
+                    arm.guard.is_some(),
+                )
+            });
+            if let Some(missing) = self.clauses(clauses, std::slice::from_ref(&subject.ty)) {
+                let pattern = self.show_all(&missing);
+                self.diagnostics.push(
+                    Diagnostic::error(format!(
+                        "non-exhaustive patterns: `{}` not covered",
+                        pattern
+                    ))
+                    .with_code("SL0024")
+                    .with_primary(subject.span.clone(), format!("`{}` not covered", pattern))
+                    .with_note(format!(
+                        "add an arm for `{}`, or a `_` arm that matches every remaining value",
+                        pattern
+                    )),
+                );
+            }
+        }
+        for child in expr.children() {
+            self.expr(child);
+        }
+    }
+
+    /// Reports each of `clauses` that cannot match anything the clauses before it do not,
+    /// returning patterns for values that none of them match, if there are any
+    ///
+    /// Clauses with a guard might not match, so they never cover values for later clauses.
+    fn clauses<'p>(
+        &mut self,
+        clauses: impl Iterator<Item = Clause<'p>>,
+        types: &[Option<Ty>],
+    ) -> Option<Row> {
+        let mut rows = vec![];
+        let mut catch_all: Option<Span> = None;
+        for (patterns, span, guarded) in clauses {
+            let row = patterns.iter().map(|p| self.lower(p)).collect::<Row>();
+            if !self.useful(&rows, &row, types) {
+                let diagnostic = Diagnostic::warning("unreachable pattern")
+                    .with_code("SLW0003")
+                    .with_primary(span.clone(), "unreachable pattern");
+                self.diagnostics.push(match &catch_all {
+                    Some(earlier) => diagnostic.with_secondary(
+                        earlier.clone(),
+                        "matches any value, so nothing after it is reached",
+                    ),
+                    None => diagnostic
+                        .with_note("the clauses before it already match every value it matches"),
+                });
+            }
+            if guarded {
+                continue;
+            }
+            if catch_all.is_none() && row.iter().all(|p| matches!(p, Pat::Wildcard)) {
+                catch_all = Some(span);
+            }
+            rows.push(row);
+        }
+        self.missing(&rows, types)
+    }
+
+    fn lower(&self, pattern: &Pattern) -> Pat {
+        let lower_all = |patterns: &[Pattern]| patterns.iter().map(|p| self.lower(p)).collect();
+        let (constructor, fields) = match &pattern.kind {
+            PatternKind::Variable(_) | PatternKind::Wildcard => return Pat::Wildcard,
+            PatternKind::Literal(Literal::Integer(i)) => (Constructor::Integer(*i), vec![]),
+            PatternKind::Literal(Literal::Unsigned(u)) => (Constructor::Unsigned(*u), vec![]),
+            PatternKind::Literal(Literal::Float(f)) => (Constructor::Float(*f), vec![]),
+            PatternKind::Literal(Literal::String(s)) => (Constructor::String(s.clone()), vec![]),
+            PatternKind::Literal(Literal::Bool(b)) => (Constructor::Bool(*b), vec![]),
+            PatternKind::List(patterns) => (Constructor::List(patterns.len()), lower_all(patterns)),
+            PatternKind::Tuple(patterns) => {
+                (Constructor::Tuple(patterns.len()), lower_all(patterns))
+            }
+            PatternKind::Variant(enum_name, variant, patterns) => (
+                Constructor::Variant(enum_name.clone(), variant.clone()),
+                lower_all(patterns),
+            ),
+            // only binds some of the fields, so the others can be anything
+            PatternKind::StructVariant(enum_name, variant, _) => {
+                let constructor = Constructor::Variant(enum_name.clone(), variant.clone());
+                let fields = vec![Pat::Wildcard; self.arity(&constructor)];
+                (constructor, fields)
+            }
+        };
+        Pat::Constructor(constructor, fields)
+    }
+
+    fn variant(&self, enum_name: &str, name: &str) -> Option<&Variant> {
+        self.enums
+            .get(enum_name)?
+            .variants
+            .iter()
+            .find(|v| variant_name(v) == name)
+    }
+
+    fn arity(&self, constructor: &Constructor) -> usize {
+        match constructor {
+            Constructor::Tuple(n) | Constructor::List(n) => *n,
+            Constructor::Variant(enum_name, name) => match self.variant(enum_name, name) {
+                Some(Variant::Tuple(_, types)) => types.len(),
+                Some(Variant::Struct(_, fields)) => fields.len(),
+                Some(Variant::Unit(_)) | None => 0,
+            },
+            Constructor::Integer(_)
+            | Constructor::Unsigned(_)
+            | Constructor::Float(_)
+            | Constructor::String(_)
+            | Constructor::Bool(_) => 0,
+        }
+    }
+
+    /// Types of the values inside a value of type `ty` made by `constructor`
+    fn field_types(&self, constructor: &Constructor, ty: Option<&Ty>) -> Vec<Option<Ty>> {
+        let ty = ty.map(peel);
+        match (constructor, ty) {
+            (Constructor::Tuple(_), Some(Ty::Tuple(types))) => {
+                types.iter().cloned().map(Some).collect()
+            }
+            (Constructor::List(n), Some(Ty::List(inner) | Ty::Array(inner, _))) => {
+                vec![Some((**inner).clone()); *n]
+            }
+            (Constructor::Variant(enum_name, name), _) => {
+                let types = match self.variant(enum_name, name) {
+                    Some(Variant::Tuple(_, types)) => types.iter().collect(),
+                    Some(Variant::Struct(_, fields)) => fields.iter().map(|f| &f.r#type).collect(),
+                    Some(Variant::Unit(_)) | None => vec![],
+                };
+                let args = match ty {
+                    Some(Ty::Named(_, args)) => self.enums[enum_name.as_str()]
+                        .generics
+                        .iter()
+                        .cloned()
+                        .zip(args.iter().cloned())
+                        .collect(),
+                    _ => HashMap::new(),
+                };
+                types
+                    .into_iter()
+                    .map(|t| Some(Ty::from_type(t).substitute(&args)))
+                    .collect()
+            }
+            _ => vec![None; self.arity(constructor)],
+        }
+    }
+
+    /// Every constructor of a value of type `ty` that patterns using `used` match on,
+    /// or `None` if there are too many to list, such as for strings
+    fn constructors(&self, used: &[Constructor], ty: Option<&Ty>) -> Option<Vec<Constructor>> {
+        match used.first()? {
+            Constructor::Variant(enum_name, _) => Some(
+                self.enums
+                    .get(enum_name.as_str())?
+                    .variants
+                    .iter()
+                    .map(|v| Constructor::Variant(enum_name.clone(), variant_name(v).to_string()))
+                    .collect(),
+            ),
+            Constructor::Tuple(n) => Some(vec![Constructor::Tuple(*n)]),
+            Constructor::Bool(_) => Some(vec![Constructor::Bool(false), Constructor::Bool(true)]),
+            Constructor::List(_) => match ty.map(peel) {
+                Some(Ty::Array(_, n)) => Some(vec![Constructor::List(*n)]),
+                _ => None,
+            },
+            Constructor::Integer(_) => {
+                let (min, max) = match ty.map(peel) {
+                    Some(ty @ (Ty::Unsigned(bits) | Ty::Integer(bits))) if *bits <= 16 => {
+                        ty.integer_range()?
+                    }
+                    _ => return None,
+                };
+                // smaller values first, so that they are the ones reported as missing
+                Some(
+                    (0..=max as i128)
+                        .chain((min..0).rev())
+                        .map(Constructor::Integer)
+                        .collect(),
+                )
+            }
+            Constructor::Unsigned(_) | Constructor::Float(_) | Constructor::String(_) => None,
+        }
+    }
+
+    /// Whether `row` matches a value that none of `rows` match
+    fn useful(&self, rows: &[Row], row: &[Pat], types: &[Option<Ty>]) -> bool {
+        let Some((head, rest)) = row.split_first() else {
+            return rows.is_empty();
+        };
+        let (ty, rest_types) = types
+            .split_first()
+            .map_or((None, &[][..]), |(t, r)| (t.as_ref(), r));
+        let specialized = |constructor: &Constructor, args: Vec<Pat>| {
+            let rows = specialize(rows, constructor, args.len());
+            let row = args
+                .into_iter()
+                .chain(rest.iter().cloned())
+                .collect::<Row>();
+            let mut types = self.field_types(constructor, ty);
+            types.extend(rest_types.iter().cloned());
+            self.useful(&rows, &row, &types)
+        };
+        match head {
+            Pat::Constructor(constructor, args) => specialized(constructor, args.clone()),
+            Pat::Wildcard => {
+                let used = heads(rows);
+                match self.constructors(&used, ty) {
+                    Some(all) if all.iter().all(|c| used.contains(c)) => all
+                        .iter()
+                        .any(|c| specialized(c, vec![Pat::Wildcard; self.arity(c)])),
+                    _ => self.useful(&default(rows), rest, rest_types),
+                }
+            }
+        }
+    }
+
+    /// Patterns for values of `types` that none of `rows` match, if there are any
+    fn missing(&self, rows: &[Row], types: &[Option<Ty>]) -> Option<Row> {
+        let Some((ty, rest_types)) = types.split_first() else {
+            return rows.is_empty().then(Vec::new);
+        };
+        let ty = ty.as_ref();
+        let used = heads(rows);
+        let all = self.constructors(&used, ty);
+        if let Some(all) = &all
+            && all.iter().all(|c| used.contains(c))
+        {
+            return all.iter().find_map(|constructor| {
+                let arity = self.arity(constructor);
+                let mut types = self.field_types(constructor, ty);
+                types.extend(rest_types.iter().cloned());
+                let mut missing = self.missing(&specialize(rows, constructor, arity), &types)?;
+                let rest = missing.split_off(arity);
+                Some(
+                    std::iter::once(Pat::Constructor(constructor.clone(), missing))
+                        .chain(rest)
+                        .collect(),
+                )
+            });
+        }
+        let mut missing = self.missing(&default(rows), rest_types)?;
+        let unused = match all {
+            Some(all) => all.into_iter().find(|c| !used.contains(c)),
+            None => unused(&used),
+        };
+        let head = match unused {
+            Some(constructor) => {
+                let fields = vec![Pat::Wildcard; self.arity(&constructor)];
+                Pat::Constructor(constructor, fields)
+            }
+            None => Pat::Wildcard,
+        };
+        missing.insert(0, head);
+        Some(missing)
+    }
+
+    /// Writes `pat` as Soulite code
+    fn show(&self, pat: &Pat) -> String {
+        let Pat::Constructor(constructor, args) = pat else {
+            return String::from("_");
+        };
+        match constructor {
+            Constructor::Integer(i) => i.to_string(),
+            Constructor::Unsigned(u) => u.to_string(),
+            Constructor::Float(f) => format!("{:?}", f),
+            Constructor::String(s) => format!("\"{}\"", s),
+            Constructor::Bool(b) => b.to_string(),
+            Constructor::Variant(enum_name, name) => match self.variant(enum_name, name) {
+                Some(Variant::Struct(..)) => format!("{}.{}{{}}", enum_name, name),
+                _ if args.is_empty() => format!("{}.{}", enum_name, name),
+                _ => format!("{}.{}({})", enum_name, name, self.show_all(args)),
+            },
+            Constructor::Tuple(_) => format!("({})", self.show_all(args)),
+            Constructor::List(_) => format!("[{}]", self.show_all(args)),
+        }
+    }
+
+    fn show_all(&self, pats: &[Pat]) -> String {
+        pats.iter()
+            .map(|p| self.show(p))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+fn variant_name(variant: &Variant) -> &str {
+    match variant {
+        Variant::Unit(name) | Variant::Tuple(name, _) | Variant::Struct(name, _) => name,
+    }
+}
+
+/// The type that patterns match on through any references
+fn peel(ty: &Ty) -> &Ty {
+    match ty {
+        Ty::Reference(inner) => peel(inner),
+        _ => ty,
+    }
+}
+
+/// The constructors the first pattern of each row matches on, without duplicates
+fn heads(rows: &[Row]) -> Vec<Constructor> {
+    let mut used = vec![];
+    for row in rows {
+        if let Some(Pat::Constructor(constructor, _)) = row.first()
+            && !used.contains(constructor)
+        {
+            used.push(constructor.clone());
+        }
+    }
+    used
+}
+
+/// The rows that match a value made by `constructor`, with its `arity` fields
+/// in place of their first pattern
+fn specialize(rows: &[Row], constructor: &Constructor, arity: usize) -> Vec<Row> {
+    rows.iter()
+        .filter_map(|row| {
+            let (head, rest) = row.split_first()?;
+            let fields = match head {
+                Pat::Constructor(c, args) if c == constructor => args.clone(),
+                Pat::Constructor(..) => return None,
+                Pat::Wildcard => vec![Pat::Wildcard; arity],
+            };
+            Some(fields.into_iter().chain(rest.iter().cloned()).collect())
+        })
+        .collect()
+}
+
+/// The rows whose first pattern matches any value, without that pattern
+fn default(rows: &[Row]) -> Vec<Row> {
+    rows.iter()
+        .filter(|row| matches!(row.first(), Some(Pat::Wildcard)))
+        .map(|row| row[1..].to_vec())
+        .collect()
+}
+
+/// A constructor of the same kind as `used` that is not among them,
+/// for kinds with too many constructors to list
+fn unused(used: &[Constructor]) -> Option<Constructor> {
+    match used.first()? {
+        Constructor::Integer(_) | Constructor::Unsigned(_) => {
+            (0..).map(Constructor::Integer).find(|c| !used.contains(c))
+        }
+        Constructor::List(_) => (0..).map(Constructor::List).find(|c| !used.contains(c)),
+        _ => None,
+    }
+}
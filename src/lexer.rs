@@ -1,7 +1,16 @@
+use std::rc::Rc;
+
 use logos::{Lexer, Logos};
 
+use crate::{
+    diagnostic::Diagnostic,
+    span::{SourceFile, Span},
+};
+
 #[derive(Logos, Clone, Debug, PartialEq)]
 #[logos(skip r" +")]
+#[logos(extras = Extras)]
+#[logos(error = LexError)]
 pub enum Token {
     /// First character must be a lowercase letter,
     /// rest must be either letters or numbers
@@ -22,13 +31,16 @@ pub enum Token {
     #[regex(r"\p{Lu}[\p{L}\d]*[\p{Ll}\d][\p{L}\d]*")]
     Type,
 
-    #[regex(r"(?:\d+\.\d*|\.\d+)")]
+    #[regex(r"(?:\d+\.\d+|\.\d+)")]
     Float,
 
     #[regex(r"\d+")]
+    #[regex(r"\d+[\p{L}_][\p{L}\d_]*", invalid_number)]
+    #[regex(r"\d+(?:\.\d+){2,}", invalid_number)]
     Integer,
 
     #[regex(r#""(?:[^"]|\\")*""#)]
+    #[regex(r#""(?:[^"]|\\")*"#, unterminated_string)]
     String,
 
     #[regex(r"\\[^\n]*")]
@@ -220,11 +232,103 @@ pub enum Token {
     #[token("<|=")]
     PipeLeftAssign,
 
+    #[token("break")]
+    Break,
+
+    #[token("continue")]
+    Continue,
+
     #[token("\t")]
+    #[regex(r" +\t", stray_tab)]
     Tab,
 
     #[token("\n")]
     Newline,
+
+    /// Never lexed, only matches characters that start no other token
+    #[regex(r"[^\n]", invalid_character, priority = 0)]
+    Invalid,
+}
+
+/// Input that cannot be lexed into a [`Token`]
+#[derive(Clone, Debug, PartialEq)]
+pub enum LexError {
+    /// A character that starts no token, such as `` ` ``
+    InvalidCharacter(char, Span),
+    /// The opening `"` of a string that is never closed
+    UnterminatedString(Span),
+    /// Digits followed by letters, or more than one decimal point
+    InvalidNumber(Span),
+    /// Spaces followed by a tab, since only tabs can indent
+    StrayTab(Span),
+}
+
+impl Default for LexError {
+    /// Only needed by logos for input that no pattern matches,
+    /// which [`Token::Invalid`] matches instead
+    fn default() -> Self {
+        LexError::InvalidCharacter('\0', Span::default())
+    }
+}
+
+fn invalid_character(lex: &mut Lexer<Token>) -> Result<(), LexError> {
+    let c = lex.slice().chars().next().unwrap_or_default();
+    Err(LexError::InvalidCharacter(c, lex.token_span()))
+}
+
+fn unterminated_string(lex: &mut Lexer<Token>) -> Result<(), LexError> {
+    let start = lex.span().start;
+    Err(LexError::UnterminatedString(
+        lex.extras.file.span(start..start + 1),
+    ))
+}
+
+fn invalid_number(lex: &mut Lexer<Token>) -> Result<(), LexError> {
+    Err(LexError::InvalidNumber(lex.token_span()))
+}
+
+fn stray_tab(lex: &mut Lexer<Token>) -> Result<(), LexError> {
+    Err(LexError::StrayTab(lex.token_span()))
+}
+
+impl From<LexError> for Diagnostic {
+    fn from(error: LexError) -> Self {
+        match error {
+            LexError::InvalidCharacter(c, span) => {
+                Diagnostic::error(format!("invalid character '{}'", c.escape_debug()))
+                    .with_code("SL0007")
+                    .with_primary(span, "not part of any token")
+            }
+            LexError::UnterminatedString(span) => Diagnostic::error("unterminated string")
+                .with_code("SL0008")
+                .with_primary(span, "this `\"` is never closed")
+                .with_note("a `\"` inside a string is written as `\\\"`"),
+            LexError::InvalidNumber(span) => Diagnostic::error("invalid numeric literal")
+                .with_code("SL0009")
+                .with_primary(span, "not a valid number")
+                .with_note(
+                    "numbers are digits with at most one decimal point, such as `12` or `3.14`",
+                ),
+            LexError::StrayTab(span) => Diagnostic::error("tab after spaces")
+                .with_code("SL0010")
+                .with_primary(span.clone(), "spaces before this tab")
+                .with_suggestion("indent with tabs only", span, "\t"),
+        }
+    }
+}
+
+/// State carried by the lexer for the parser
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Extras {
+    /// One entry per loop being parsed, innermost last, set to
+    /// `true` when it is an infinite loop that can `break` with a value
+    pub loops: Vec<bool>,
+    /// Items of `std` that the parsed syntax relies on, such as `HashMap`
+    pub std_items: Vec<String>,
+    /// File being lexed, for the [`Span`]s of the parsed nodes
+    pub file: Rc<SourceFile>,
+    /// Syntax errors recovered from and warnings found so far
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 impl Token {
@@ -279,7 +383,7 @@ pub trait CheckToken {
     fn is_type(&self) -> bool;
 }
 
-impl CheckToken for Option<Result<Token, ()>> {
+impl CheckToken for Option<Result<Token, LexError>> {
     fn is_arrow(&self) -> bool {
         self == &Some(Ok(Token::Arrow))
     }
@@ -324,24 +428,36 @@ impl CheckToken for Option<Result<Token, ()>> {
         self == &Some(Ok(Token::Tab))
     }
 
+    /// Whether this can start a type, including generic types such as `t` or `'T`
     fn is_type(&self) -> bool {
         self == &Some(Ok(Token::Type))
+            || self == &Some(Ok(Token::Identifier))
+            || self == &Some(Ok(Token::ConstIdentifier))
+            || self == &Some(Ok(Token::Tick))
             || self == &Some(Ok(Token::LeftParen))
             || self == &Some(Ok(Token::LeftBracket))
+            || self == &Some(Ok(Token::LeftBrace))
             || self == &Some(Ok(Token::Star))
     }
 }
 
 pub trait Lookahead {
-    fn skip_indents(&mut self) -> (usize, Option<Result<Token, ()>>);
+    fn skip_indents(&mut self) -> (usize, Option<Result<Token, LexError>>);
     fn step_before(&mut self);
-    fn step(&mut self) -> Option<Result<Token, ()>>;
-    fn peek(&mut self) -> Option<Result<Token, ()>>;
-    fn lookahead(&mut self) -> Option<Result<Token, ()>>;
+    fn step(&mut self) -> Option<Result<Token, LexError>>;
+    fn peek(&mut self) -> Option<Result<Token, LexError>>;
+    fn lookahead(&mut self) -> Option<Result<Token, LexError>>;
+    fn lookahead2(&mut self) -> Option<Result<Token, LexError>>;
+    fn indent_level(&self) -> usize;
+    fn is_adjacent(&mut self) -> bool;
+    fn split(&mut self, len: usize);
+    fn current(&self) -> Option<Result<Token, LexError>>;
+    fn token_span(&self) -> Span;
+    fn span_from(&self, start: usize) -> Span;
 }
 
 impl<'source> Lookahead for Lexer<'source, Token> {
-    fn skip_indents(&mut self) -> (usize, Option<Result<Token, ()>>) {
+    fn skip_indents(&mut self) -> (usize, Option<Result<Token, LexError>>) {
         let mut tok;
         for i in 0.. {
             tok = self.peek();
@@ -359,16 +475,66 @@ impl<'source> Lookahead for Lexer<'source, Token> {
         }
     }
 
-    fn step(&mut self) -> Option<Result<Token, ()>> {
+    fn step(&mut self) -> Option<Result<Token, LexError>> {
         self.step_before();
         self.next()
     }
 
-    fn peek(&mut self) -> Option<Result<Token, ()>> {
+    fn peek(&mut self) -> Option<Result<Token, LexError>> {
         self.clone().next()
     }
 
-    fn lookahead(&mut self) -> Option<Result<Token, ()>> {
+    fn lookahead(&mut self) -> Option<Result<Token, LexError>> {
         self.clone().step()
     }
+
+    /// Peeks at the token after the next one
+    fn lookahead2(&mut self) -> Option<Result<Token, LexError>> {
+        let mut lex = self.clone();
+        lex.next();
+        lex.next()
+    }
+
+    /// Whether the next token directly follows the current one without spaces
+    fn is_adjacent(&mut self) -> bool {
+        let end = self.span().end;
+        let mut lex = self.clone();
+        lex.next();
+        lex.span().start == end
+    }
+
+    /// Only keeps the first `len` bytes of the current token so the rest is lexed
+    /// again, such as the `]` of `>]` when closing generics inside a list type
+    fn split(&mut self, len: usize) {
+        let mut lex = Lexer::with_extras(self.source(), self.extras.clone());
+        lex.bump(self.span().start + len);
+        *self = lex;
+    }
+
+    /// Lexes the current token again, such as to get the [`LexError`] it was
+    fn current(&self) -> Option<Result<Token, LexError>> {
+        let mut lex = Lexer::with_extras(self.source(), self.extras.clone());
+        lex.bump(self.span().start);
+        lex.next()
+    }
+
+    /// Number of tabs at the start of the line containing the current token
+    fn indent_level(&self) -> usize {
+        let source = self.source();
+        let line_start = source[..self.span().start].rfind('\n').map_or(0, |i| i + 1);
+        source[line_start..]
+            .chars()
+            .take_while(|c| *c == '\t')
+            .count()
+    }
+
+    /// [`Span`] of the current token
+    fn token_span(&self) -> Span {
+        self.extras.file.span(self.span())
+    }
+
+    /// [`Span`] from byte `start` to the end of the current token
+    fn span_from(&self, start: usize) -> Span {
+        self.extras.file.span(start..self.span().end)
+    }
 }
@@ -1,16 +1,30 @@
 use std::{
     fs::File,
-    io::{self, Write},
-    process::Command,
+    io::Write,
+    process::{Command, ExitCode},
+    rc::Rc,
 };
 
 use clap::Parser;
 
-use crate::ast::ToRust;
+use crate::{
+    ast::ToRust,
+    diagnostic::{Diagnostic, Emitter, MessageFormat},
+    span::{SourceFile, SourceMap},
+};
 
 mod ast;
+mod consteval;
+mod diagnostic;
+mod exhaustive;
 mod lexer;
+mod mutability;
 mod parser;
+mod resolve;
+mod rustc;
+mod span;
+mod traits;
+mod typeck;
 
 #[derive(Parser)]
 #[command(version, about)]
@@ -31,38 +45,105 @@ struct Cli {
     /// Example: `soulite -c build/main src/main.sl`
     #[arg(short, long, value_name = "EXE")]
     compile: Option<String>,
+
+    /// Set the format of errors and warnings
+    ///
+    /// Example: `soulite --message-format=json main.sl`
+    #[arg(long, value_enum, value_name = "FORMAT", default_value_t)]
+    message_format: MessageFormat,
 }
 
-fn main() -> Result<(), String> {
+fn main() -> ExitCode {
     let cli = Cli::parse();
-    let soulite_tree = parser::parse::<false>(&cli.soulite_file)?;
-    let rust_tree = syn::parse_file(&soulite_tree.to_rust())
-        .map_err(|e| format!("Failed to parse Soulite file: {}", e))?;
-    let rust_code = prettyplease::unparse(&rust_tree);
+    let mut emitter = Emitter::new(cli.message_format);
+    match run(cli, &mut emitter) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(diagnostic) => {
+            emitter.emit(&diagnostic);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Compiles the Soulite file, emitting its diagnostics as they are found
+/// and returning the one that stopped compilation
+fn run(cli: Cli, emitter: &mut Emitter) -> Result<(), Diagnostic> {
+    let source = std::fs::read_to_string(&cli.soulite_file).map_err(|e| {
+        Diagnostic::error(format!("failed to read file {}: {}", cli.soulite_file, e))
+    })?;
+    let file = Rc::new(SourceFile::new(&cli.soulite_file, &source));
+    emitter.add_file(file.clone());
+    let (mut soulite_tree, diagnostics) = parser::parse::<false>(file.clone());
+    report(emitter, &diagnostics)?;
+    report(emitter, &resolve::resolve(&soulite_tree))?;
+    report(emitter, &traits::check(&mut soulite_tree))?;
+    report(emitter, &consteval::evaluate(&mut soulite_tree))?;
+    report(emitter, &typeck::check(&mut soulite_tree))?;
+    report(emitter, &exhaustive::check(&soulite_tree))?;
+    report(emitter, &mutability::check(&soulite_tree))?;
+    let (rust_code, source_map) = SourceMap::new(file, &soulite_tree.to_rust());
+    let rust_tree = syn::parse_file(&rust_code)
+        .map_err(|e| Diagnostic::error(format!("failed to parse Soulite file: {}", e)))?;
+    let pretty_code = prettyplease::unparse(&rust_tree);
     let rust_file = cli.transpile.unwrap_or_else(|| {
         std::path::Path::new(&cli.soulite_file)
             .with_extension("rs")
             .to_string_lossy()
             .to_string()
     });
-    let mut file =
-        File::create(&rust_file).map_err(|e| format!("Failed to create rust file: {}", e))?;
-    file.write_all(rust_code.as_bytes())
-        .map_err(|e| format!("Failed to write to rust file: {}", e))?;
-    if let Some(file_name) = cli.compile {
-        let output = Command::new("rustc")
-            .arg("--color=always")
-            .arg(rust_file)
-            .arg("-o")
-            .arg(&file_name)
-            .output()
-            .map_err(|e| format!("Failed to execute rustc: {}", e))?;
-        if output.status.success() {
-            println!("Compiled successfully to `{}`.", file_name);
-        } else {
-            io::stderr().write_all(&output.stderr).unwrap();
-            return Err(String::from("Failed to compile generated Rust file."));
+    let write_rust = |code: &str| {
+        let mut file = File::create(&rust_file)
+            .map_err(|e| Diagnostic::error(format!("failed to create rust file: {}", e)))?;
+        file.write_all(code.as_bytes())
+            .map_err(|e| Diagnostic::error(format!("failed to write to rust file: {}", e)))
+    };
+    let Some(file_name) = cli.compile else {
+        return write_rust(&pretty_code);
+    };
+    // rustc compiles the unformatted code first, so the spans it reports line up with the source map
+    write_rust(&rust_code)?;
+    let output = Command::new("rustc")
+        .arg("--error-format=json")
+        .arg("--cap-lints=allow")
+        .arg(&rust_file)
+        .arg("-o")
+        .arg(&file_name)
+        .output()
+        .map_err(|e| Diagnostic::error(format!("failed to execute rustc: {}", e)))?;
+    write_rust(&pretty_code)?;
+    let mut errors = 0;
+    for line in String::from_utf8_lossy(&output.stderr).lines() {
+        if let Some(diagnostic) = rustc::translate(line, &rust_file, &source_map) {
+            errors += diagnostic.is_error() as usize;
+            emitter.emit(&diagnostic);
         }
     }
+    if !output.status.success() {
+        return Err(if errors > 0 {
+            aborting(errors)
+        } else {
+            Diagnostic::error("failed to compile generated Rust file")
+        });
+    }
+    println!("Compiled successfully to `{}`.", file_name);
     Ok(())
 }
+
+/// Emits `diagnostics`, failing if any of them is an error
+fn report(emitter: &Emitter, diagnostics: &[Diagnostic]) -> Result<(), Diagnostic> {
+    for diagnostic in diagnostics {
+        emitter.emit(diagnostic);
+    }
+    match diagnostics.iter().filter(|d| d.is_error()).count() {
+        0 => Ok(()),
+        errors => Err(aborting(errors)),
+    }
+}
+
+fn aborting(errors: usize) -> Diagnostic {
+    Diagnostic::error(format!(
+        "aborting due to {} previous error{}",
+        errors,
+        if errors == 1 { "" } else { "s" }
+    ))
+}
@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+
+use crate::{
+    ast::{
+        AssignType, Expr, ExprKind, Field, Function, Pattern, PatternKind, Program, is_assignment,
+    },
+    diagnostic::Diagnostic,
+    span::Span,
+    typeck::Ty,
+};
+
+/// Checks that only bindings declared with `;=` and fields declared with `;`
+/// are assigned to after their declaration
+///
+/// Fields are found through the types from [`crate::typeck::check`], so it runs after that.
+pub fn check(program: &Program) -> Vec<Diagnostic> {
+    let mut checker = Checker {
+        fields: program
+            .structs
+            .iter()
+            .map(|s| (s.name.as_str(), s.fields.as_slice()))
+            .collect(),
+        ..Default::default()
+    };
+    checker.scopes.push(vec![]);
+    for variable in &program.variables {
+        if let ExprKind::Assign {
+            pattern,
+            assign_type,
+            mutable,
+            ..
+        } = &variable.kind
+        {
+            let binding = match assign_type {
+                AssignType::Static if *mutable => Binding::Mutable,
+                _ => Binding::Immutable,
+            };
+            checker.pattern(pattern, binding);
+        }
+    }
+    let methods = program
+        .structs
+        .iter()
+        .flat_map(|s| &s.methods)
+        .chain(program.impls.iter().flat_map(|i| &i.methods));
+    for function in methods.chain(&program.functions) {
+        checker.function(function);
+    }
+    checker.diagnostics
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Binding {
+    /// Declared with `;=`
+    Mutable,
+    /// Declared with `:=`
+    Immutable,
+    /// Bound by a `for` loop or a `match` arm
+    Pattern,
+    Parameter,
+}
+
+#[derive(Default)]
+struct Checker<'a> {
+    /// Fields of each struct by its name
+    fields: HashMap<&'a str, &'a [Field]>,
+    /// Bindings of each block being checked, innermost last, starting with the globals
+    scopes: Vec<Vec<(String, Span, Binding)>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Checker<'a> {
+    fn declare(&mut self, name: &str, span: &Span, binding: Binding) {
+        self.scopes
+            .last_mut()
+            .expect("bindings are declared inside a scope")
+            .push((name.to_string(), span.clone(), binding));
+    }
+
+    fn lookup(&self, name: &str) -> Option<&(String, Span, Binding)> {
+        self.scopes
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev())
+            .find(|(n, _, _)| n == name)
+    }
+
+    fn function(&mut self, function: &Function) {
+        let signature = &function.signature;
+        self.scopes.push(vec![]);
+        for name in &signature.param_names {
+            self.declare(name, &signature.span, Binding::Parameter);
+        }
+        self.block(&function.body);
+        for equation in &function.equations {
+            self.scopes.push(vec![]);
+            for pattern in &equation.parameters_list {
+                self.pattern(pattern, Binding::Parameter);
+            }
+            if let Some(guard) = &equation.guard {
+                self.expr(guard);
+            }
+            self.block(&equation.body);
+            self.scopes.pop();
+        }
+        self.scopes.pop();
+    }
+
+    fn block(&mut self, body: &[Expr]) {
+        self.scopes.push(vec![]);
+        for expr in body {
+            self.expr(expr);
+        }
+        self.scopes.pop();
+    }
+
+    fn pattern(&mut self, pattern: &Pattern, binding: Binding) {
+        match &pattern.kind {
+            PatternKind::Variable(name) => self.declare(name, &pattern.span, binding),
+            PatternKind::List(patterns)
+            | PatternKind::Tuple(patterns)
+            | PatternKind::Variant(_, _, patterns) => {
+                for pattern in patterns {
+                    self.pattern(pattern, binding);
+                }
+            }
+            PatternKind::StructVariant(_, _, fields) => {
+                for field in fields {
+                    self.declare(field, &pattern.span, binding);
+                }
+            }
+            PatternKind::Literal(_) | PatternKind::Wildcard => (),
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Binary { op, lhs, rhs } if is_assignment(op) => {
+                self.expr(rhs);
+                self.expr(lhs);
+                self.assign(lhs);
+            }
+            ExprKind::Assign {
+                pattern,
+                mutable,
+                value,
+                ..
+            } => {
+                self.expr(value);
+                let binding = if *mutable {
+                    Binding::Mutable
+                } else {
+                    Binding::Immutable
+                };
+                self.pattern(pattern, binding);
+            }
+            ExprKind::Closure { args, body } => {
+                self.scopes.push(vec![]);
+                for arg in args {
+                    self.declare(arg, &expr.span, Binding::Parameter);
+                }
+                self.expr(body);
+                self.scopes.pop();
+            }
+            ExprKind::For {
+                pattern,
+                iterable,
+                body,
+            } => {
+                self.expr(iterable);
+                self.scopes.push(vec![]);
+                self.pattern(pattern, Binding::Pattern);
+                self.block(body);
+                self.scopes.pop();
+            }
+            ExprKind::While { condition, body } => {
+                self.expr(condition);
+                self.block(body);
+            }
+            ExprKind::Loop(body) => self.block(body),
+            ExprKind::Match { subject, arms } => {
+                self.expr(subject);
+                for arm in arms {
+                    self.scopes.push(vec![]);
+                    self.pattern(&arm.pattern, Binding::Pattern);
+                    if let Some(guard) = &arm.guard {
+                        self.expr(guard);
+                    }
+                    self.block(&arm.body);
+                    self.scopes.pop();
+                }
+            }
+            ExprKind::Reference(inner)
+            | ExprKind::AnonParam(inner)
+            | ExprKind::Some(inner)
+            | ExprKind::Ok(inner)
+            | ExprKind::Err(inner)
+            | ExprKind::Break(Some(inner)) => self.expr(inner),
+            ExprKind::List(items)
+            | ExprKind::Tuple(items)
+            | ExprKind::Set(items)
+            | ExprKind::Call { args: items, .. }
+            | ExprKind::Variant { args: items, .. } => {
+                for item in items {
+                    self.expr(item);
+                }
+            }
+            ExprKind::Map(entries) => {
+                for (key, value) in entries {
+                    self.expr(key);
+                    self.expr(value);
+                }
+            }
+            ExprKind::Entry { map: lhs, key: rhs } | ExprKind::Binary { lhs, rhs, .. } => {
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+            ExprKind::Ternary {
+                condition,
+                if_true,
+                if_false,
+            } => {
+                self.expr(condition);
+                self.expr(if_true);
+                self.expr(if_false);
+            }
+            ExprKind::This
+            | ExprKind::None
+            | ExprKind::Literal(_)
+            | ExprKind::Variable(_)
+            | ExprKind::Break(None)
+            | ExprKind::Continue => (),
+        }
+    }
+
+    /// Reports assigning to `target` if the variable or a field it belongs to is not mutable
+    fn assign(&mut self, target: &Expr) {
+        if let Some((owner, field)) = self.immutable_field(target) {
+            let diagnostic = Diagnostic::error(format!(
+                "cannot assign to immutable field `{}` of `{}`",
+                field.name, owner
+            ))
+            .with_code("SL0019")
+            .with_primary(target.span.clone(), "cannot assign")
+            .with_secondary(
+                field.span.clone(),
+                format!("`{}` is declared here", field.name),
+            )
+            .with_note(format!(
+                "declare the field with `;` instead of `:` to make it mutable: `{}; {}`",
+                field.name,
+                Ty::from_type(&field.r#type)
+            ));
+            self.diagnostics.push(diagnostic);
+            return;
+        }
+        let Some((name, root)) = root_variable(target) else {
+            return;
+        };
+        let Some((_, declared, binding)) = self.lookup(name) else {
+            return;
+        };
+        let (declared, binding) = (declared.clone(), *binding);
+        let diagnostic = match binding {
+            Binding::Mutable => return,
+            // assigning to the variable itself rather than to one of its fields or entries
+            Binding::Parameter if target.span == root.span => {
+                Diagnostic::error(format!("cannot assign to immutable parameter `{}`", name))
+                    .with_primary(target.span.clone(), "cannot assign to a parameter")
+            }
+            _ if target.span == root.span => {
+                Diagnostic::error(format!("cannot assign twice to immutable `{}`", name))
+                    .with_primary(target.span.clone(), "cannot assign twice")
+            }
+            _ => Diagnostic::error(format!(
+                "cannot assign to part of `{}`, as `{}` is not declared as mutable",
+                name, name
+            ))
+            .with_primary(target.span.clone(), "cannot assign"),
+        };
+        let diagnostic = diagnostic.with_code("SL0019");
+        self.diagnostics.push(match binding {
+            Binding::Parameter => diagnostic
+                .with_secondary(declared, format!("`{}` is a parameter here", name))
+                .with_note(format!(
+                    "copy it into a mutable variable first: `{} ;= {}`",
+                    name, name
+                )),
+            Binding::Pattern => diagnostic
+                .with_secondary(declared, format!("`{}` is bound by a pattern here", name))
+                .with_note(format!(
+                    "copy it into a mutable variable first: `{} ;= {}`",
+                    name, name
+                )),
+            _ => diagnostic
+                .with_secondary(declared, format!("first assignment to `{}`", name))
+                .with_note(format!(
+                    "declare `{}` with `;=` instead of `:=` to make it mutable",
+                    name
+                )),
+        });
+    }
+
+    /// The first field along `target`, such as `age` in `person.age{0}`,
+    /// that is declared immutable, along with the name of its struct
+    fn immutable_field(&self, target: &Expr) -> Option<(&'a str, &'a Field)> {
+        match &target.kind {
+            ExprKind::Binary { op, lhs, rhs } if op == "." => {
+                let name = match &rhs.kind {
+                    ExprKind::Variable(name) => Some(name),
+                    ExprKind::Entry { map, .. } => match &map.kind {
+                        ExprKind::Variable(name) => Some(name),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                let owner = match &lhs.ty {
+                    Some(Ty::Named(owner, _)) => Some(owner),
+                    Some(Ty::Reference(inner)) => match &**inner {
+                        Ty::Named(owner, _) => Some(owner),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                let field = name.zip(owner).and_then(|(name, owner)| {
+                    let (owner, fields) = self.fields.get_key_value(owner.as_str())?;
+                    let field = fields.iter().find(|f| f.name == *name)?;
+                    Some((*owner, field))
+                });
+                match field {
+                    Some((owner, field)) if !field.mutable => Some((owner, field)),
+                    _ => self.immutable_field(lhs),
+                }
+            }
+            ExprKind::Entry { map, .. } => self.immutable_field(map),
+            _ => None,
+        }
+    }
+}
+
+/// The variable whose value `target` is part of, such as `p` in `p.items{0}`,
+/// or `None` for `.` in a method, which is always mutable
+fn root_variable(target: &Expr) -> Option<(&String, &Expr)> {
+    match &target.kind {
+        ExprKind::Variable(name) => Some((name, target)),
+        ExprKind::Entry { map: inner, .. }
+        | ExprKind::Binary { lhs: inner, .. }
+        | ExprKind::AnonParam(inner) => root_variable(inner),
+        _ => None,
+    }
+}
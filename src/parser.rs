@@ -1,133 +1,322 @@
-use std::vec;
+use std::{rc::Rc, vec};
 
 use crate::{
     ast::{
-        AssignType, Expr, Function, Impl, Import, Literal, Pattern, Program, Struct, Trait, Type,
-        TypeSignature,
+        AssignType, Bound, Enum, Equation, Expr, ExprKind, Field, Function, Impl, Import, Literal,
+        MatchArm, Pattern, PatternKind, Program, Struct, Trait, Type, TypeKind, TypeSignature,
+        Variant,
     },
-    lexer::{CheckToken, Lookahead, Token},
+    diagnostic::Diagnostic,
+    lexer::{CheckToken, Extras, LexError, Lookahead, Token},
+    resolve::similar,
+    span::SourceFile,
 };
 use logos::{Lexer, Logos};
 
-pub fn parse<const IS_DEBUG: bool>(file_name: &str) -> Result<Program, String> {
-    let soulite_source = std::fs::read_to_string(file_name)
-        .map_err(|e| format!("Failed to read file {}: {}", file_name, e))?;
-    let mut lex = Token::lexer(&soulite_source);
-    let mut program = Program {
-        imports: vec![],
-        traits: vec![],
-        structs: vec![],
-        impls: vec![],
-        functions: vec![],
-        variables: vec![],
-    };
-    loop {
-        let Some(res) = lex.next() else {
-            if IS_DEBUG {
-                println!("Finished parsing {}.", file_name);
+/// Parses as much of `file` as possible, returning the diagnostics
+/// of every syntax error along with the partial [`Program`]
+pub fn parse<const IS_DEBUG: bool>(file: Rc<SourceFile>) -> (Program, Vec<Diagnostic>) {
+    let mut program = Program::default();
+    let mut lex = Token::lexer_with_extras(
+        &file.source,
+        Extras {
+            file: file.clone(),
+            ..Default::default()
+        },
+    );
+    while let Some(res) = lex.next() {
+        if let Err(diagnostic) = parse_item::<IS_DEBUG>(&mut lex, &mut program, res) {
+            lex.extras.diagnostics.push(diagnostic);
+            recover(&mut lex, 0);
+        }
+    }
+    if IS_DEBUG {
+        println!("Finished parsing {}.", file.name);
+    }
+    add_std_items(&mut program, &lex.extras);
+    inherit_generics(&mut program);
+    collect_impl_methods(&mut program);
+    (program, lex.extras.diagnostics)
+}
+
+/// Parses the top-level item starting with `res` into `program`
+fn parse_item<const IS_DEBUG: bool>(
+    lex: &mut Lexer<Token>,
+    program: &mut Program,
+    res: Result<Token, LexError>,
+) -> Result<(), Diagnostic> {
+    let tok = res?;
+    if IS_DEBUG {
+        println!("Starting to parse: {:?}", lex.slice());
+    }
+    match tok {
+        Token::Newline | Token::Comment => (),
+        Token::Plus => program.imports.push(parse_import(lex)?),
+        Token::Identifier if is_equation(lex) => {
+            let name = lex.slice().to_string();
+            let equation = parse_equation(lex)?;
+            let span = &equation.span;
+            let Some(func) = program
+                .functions
+                .iter_mut()
+                .rev()
+                .find(|f| f.signature.name == name)
+            else {
+                return Err(Diagnostic::error(format!(
+                    "expected a type signature for `{}` before its equation",
+                    name
+                ))
+                .with_code("SL0004")
+                .with_primary(span.clone(), "equation without a type signature")
+                .with_note(format!(
+                    "declare the parameter and return types first, such as `{} n: Z64 -> Z64`",
+                    name
+                )));
+            };
+            if !func.body.is_empty() {
+                return Err(Diagnostic::error(format!(
+                    "function `{}` already has a body, so it cannot have an equation",
+                    name
+                ))
+                .with_code("SL0005")
+                .with_primary(span.clone(), "equation after a body")
+                .with_secondary(func.span.clone(), format!("`{}` is defined here", name))
+                .with_note("a function is defined either by an indented body or by equations"));
             }
-            return Ok(program);
-        };
-        let Ok(tok) = res else {
-            return err(&lex, "top-level token");
-        };
-        if IS_DEBUG {
-            println!("Starting to parse: {:?}", lex.slice());
+            let expected = func.signature.param_names.len();
+            if equation.parameters_list.len() != expected {
+                return Err(Diagnostic::error(format!(
+                    "expected {} pattern(s) for `{}`, found {}",
+                    expected,
+                    name,
+                    equation.parameters_list.len()
+                ))
+                .with_code("SL0006")
+                .with_primary(span.clone(), format!("expected {} pattern(s)", expected))
+                .with_secondary(
+                    func.signature.span.clone(),
+                    format!("`{}` takes {} parameter(s)", name, expected),
+                ));
+            }
+            func.span = func.span.to(&equation.span);
+            func.equations.push(equation);
         }
-        match tok {
-            Token::Newline | Token::Comment => continue,
-            Token::Plus => program.imports.push(parse_import(&mut lex)?),
-            Token::Identifier => {
-                let name = lex.slice().to_string();
-                let (param_names, mut tok) = parse_params(&mut lex);
-                if tok.is_colon() {
-                    tok = lex.peek();
-                    if param_names.is_empty() && !tok.is_arrow() {
-                        program.variables.push(parse_assignment(
-                            &mut lex,
-                            name,
-                            AssignType::Static,
-                            false,
-                        )?)
-                    } else {
-                        program.functions.push(parse_function(
-                            &mut lex,
-                            name,
-                            param_names,
-                            &vec![],
-                            false,
-                            1,
-                        )?)
-                    }
-                } else if tok.is_semicolon() {
+        Token::Identifier => {
+            let name = lex.slice().to_string();
+            let name_span = lex.token_span();
+            let (generics, bounds) = if lex.peek() == Some(Ok(Token::LessThan)) && lex.is_adjacent()
+            {
+                lex.next();
+                parse_generic_types(lex)?
+            } else {
+                (vec![], vec![])
+            };
+            let (param_names, mut tok) = parse_params(lex);
+            if tok.is_colon() {
+                tok = lex.peek();
+                if param_names.is_empty() && !tok.is_arrow() && !generics.is_empty() {
+                    return err(lex, "parameters or `->` after generic types");
+                } else if param_names.is_empty() && !tok.is_arrow() {
                     program.variables.push(parse_assignment(
-                        &mut lex,
-                        name,
+                        lex,
+                        Pattern::new(PatternKind::Variable(name), name_span),
                         AssignType::Static,
-                        true,
+                        false,
                     )?)
                 } else {
-                    return err(&lex, "variable or function marker");
+                    let mut function = parse_function(
+                        lex,
+                        name,
+                        name_span.range.start,
+                        param_names,
+                        &generics,
+                        false,
+                        1,
+                    )?;
+                    // declared generic types come before the ones used without a declaration
+                    function.signature.generics.splice(0..0, generics);
+                    function.signature.bounds = bounds;
+                    program.functions.push(function)
                 }
+            } else if tok.is_semicolon() {
+                program.variables.push(parse_assignment(
+                    lex,
+                    Pattern::new(PatternKind::Variable(name), name_span),
+                    AssignType::Static,
+                    true,
+                )?)
+            } else {
+                return err(lex, "variable or function marker");
             }
-            Token::ConstIdentifier => {
-                let name = lex.slice().to_string();
-                if !lex.next().is_colon() {
-                    return err(&lex, "`:` after const name");
-                }
-                program
-                    .variables
-                    .push(parse_assignment(&mut lex, name, AssignType::Const, false)?)
-            }
-            Token::Type => {
-                let name = lex.slice().to_string();
-                let mut tok = lex.next();
-                let generic_types = if tok == Some(Ok(Token::LessThan)) {
-                    let result = parse_generic_types(&mut lex)?;
-                    tok = lex.next();
-                    result
+        }
+        Token::ConstIdentifier => {
+            let name = lex.slice().to_string();
+            let name_span = lex.token_span();
+            let tok = lex.next();
+            if !tok.is_colon() {
+                let diagnostic = unexpected(lex, "`:` after const name");
+                return Err(if tok.is_assign() {
+                    diagnostic.with_suggestion(
+                        "use `:=` to declare a constant",
+                        lex.token_span(),
+                        ":=",
+                    )
                 } else {
-                    vec![]
-                };
-                match tok {
-                    Some(Ok(Token::FatArrow)) => {
-                        program
-                            .impls
-                            .push(parse_impl(&mut lex, name, generic_types)?)
-                    }
-                    Some(Ok(Token::Assign)) => {
-                        program
-                            .structs
-                            .push(parse_struct(&mut lex, name, generic_types)?)
-                    }
-                    Some(Ok(Token::Colon)) => {
-                        program
-                            .traits
-                            .push(parse_trait(&mut lex, name, generic_types)?)
-                    }
-                    _ => return err(&lex, "colon, arrow, or generic type after struct name"),
+                    diagnostic
+                });
+            }
+            program.variables.push(parse_assignment(
+                lex,
+                Pattern::new(PatternKind::Variable(name), name_span),
+                AssignType::Const,
+                false,
+            )?)
+        }
+        Token::Type => {
+            let name = lex.slice().to_string();
+            let start = lex.span().start;
+            let mut tok = lex.next();
+            let (generic_types, bounds) = if tok == Some(Ok(Token::LessThan)) {
+                let result = parse_generic_types(lex)?;
+                tok = lex.next();
+                result
+            } else {
+                (vec![], vec![])
+            };
+            let span = match tok {
+                Some(Ok(Token::FatArrow)) => {
+                    let item = parse_impl(lex, name, start, generic_types, bounds)?;
+                    let span = item.span.clone();
+                    program.impls.push(item);
+                    span
+                }
+                Some(Ok(Token::Assign)) => {
+                    let item = parse_struct(lex, name, start, generic_types, bounds)?;
+                    let span = item.span.clone();
+                    program.structs.push(item);
+                    span
                 }
+                Some(Ok(Token::Colon)) => {
+                    let item = parse_trait(lex, name, start, generic_types, bounds)?;
+                    let span = item.span.clone();
+                    program.traits.push(item);
+                    span
+                }
+                Some(Ok(Token::Pipe)) => {
+                    let item = parse_enum(lex, name, start, generic_types, bounds)?;
+                    let span = item.span.clone();
+                    program.enums.push(item);
+                    span
+                }
+                _ => {
+                    return err(lex, "colon, arrow, pipe, or generic type after type name");
+                }
+            };
+            if IS_DEBUG {
+                println!("Parsed type declaration at {}.", span);
             }
-            _ => return err(&lex, "import or declaration"),
         }
+        _ => return err(lex, "import or declaration"),
     }
+    Ok(())
 }
 
-fn parse_import(lex: &mut Lexer<Token>) -> Result<Import, String> {
+/// Gives each impl without a `<...>` list the generic types and bounds of its struct or enum,
+/// which its methods then share instead of declaring their own
+fn inherit_generics(program: &mut Program) {
+    for r#impl in &mut program.impls {
+        if !r#impl.generic_types.is_empty() {
+            continue;
+        }
+        let generics = program
+            .structs
+            .iter()
+            .find(|s| s.name == r#impl.struct_name)
+            .map(|s| (&s.generics, &s.bounds))
+            .or_else(|| {
+                program
+                    .enums
+                    .iter()
+                    .find(|e| e.name == r#impl.struct_name)
+                    .map(|e| (&e.generics, &e.bounds))
+            });
+        if let Some((generics, bounds)) = generics {
+            r#impl.generic_types = generics.clone();
+            r#impl.bounds = bounds.clone();
+            remove_generics(&mut r#impl.methods, generics);
+        }
+    }
+}
+
+/// Tells each struct the names of the methods its impls define, which its accessors must leave alone
+fn collect_impl_methods(program: &mut Program) {
+    for r#impl in &program.impls {
+        if let Some(r#struct) = program
+            .structs
+            .iter_mut()
+            .find(|s| s.name == r#impl.struct_name)
+        {
+            let names = r#impl.methods.iter().map(|m| m.signature.name.clone());
+            r#struct.impl_methods.extend(names);
+        }
+    }
+}
+
+/// Removes `generics` of the parent type from the generic types of each method
+fn remove_generics(methods: &mut [Function], generics: &[String]) {
+    for method in methods {
+        method
+            .signature
+            .generics
+            .retain(|name| !generics.contains(name));
+    }
+}
+
+/// Adds the `std` items required by the parsed syntax to the program's `std` import,
+/// creating one at the start of the file if there is none
+fn add_std_items(program: &mut Program, extras: &Extras) {
+    if extras.std_items.is_empty() {
+        return;
+    }
+    let import = match program.imports.iter_mut().find(|i| i.filename == "std") {
+        Some(import) => import,
+        None => {
+            program.imports.push(Import {
+                filename: String::from("std"),
+                items: vec![],
+                span: extras.file.span(0..0),
+            });
+            program.imports.last_mut().unwrap()
+        }
+    };
+    for item in &extras.std_items {
+        if !import.items.contains(item) {
+            import.items.push(item.clone());
+        }
+    }
+}
+
+fn parse_import(lex: &mut Lexer<Token>) -> Result<Import, Diagnostic> {
+    let start = lex.span().start;
     if !lex.next().is_identifier() {
         return err(lex, "identifier after import token `+`");
     }
     let mut import = Import {
         filename: lex.slice().to_string(),
         items: vec![],
+        span: lex.span_from(start),
     };
     if lex.next().is_colon() {
         match lex.next() {
-            Some(Ok(Token::Identifier)) => import.items.push(lex.slice().to_string()),
+            Some(Ok(Token::Identifier)) => {
+                import.items.push(lex.slice().to_string());
+                import.span = lex.span_from(start);
+            }
             Some(Ok(Token::Newline)) => {
                 let mut tab = lex.next();
                 while tab.is_tab() && lex.next().is_identifier() {
                     import.items.push(lex.slice().to_string());
+                    import.span = lex.span_from(start);
                     tab = lex.next();
                 }
             }
@@ -140,8 +329,10 @@ fn parse_import(lex: &mut Lexer<Token>) -> Result<Import, String> {
 fn parse_trait(
     lex: &mut Lexer<Token>,
     name: String,
+    start: usize,
     generics: Vec<String>,
-) -> Result<Trait, String> {
+    bounds: Vec<Bound>,
+) -> Result<Trait, Diagnostic> {
     let mut signatures = vec![];
     if !lex.next().is_newline() {
         return err(lex, "newline after `:`");
@@ -149,16 +340,18 @@ fn parse_trait(
     while lex.peek().is_tab() {
         lex.next();
         if !lex.next().is_identifier() {
-            return err(&lex, "method name after tab");
+            return err(lex, "method name after tab");
         }
         let method_name = lex.slice().to_string();
+        let method_start = lex.span().start;
         let (param_names, tok) = parse_params(lex);
         if !tok.is_colon() {
-            return err(&lex, "`:` for method");
+            return err(lex, "`:` for method");
         }
         signatures.push(parse_signature(
             lex,
             method_name,
+            method_start,
             param_names,
             &generics,
             true,
@@ -167,52 +360,160 @@ fn parse_trait(
     Ok(Trait {
         name,
         generics,
+        bounds,
         signatures,
+        span: lex.span_from(start),
     })
 }
 
 fn parse_struct(
     lex: &mut Lexer<Token>,
     name: String,
+    start: usize,
     generics: Vec<String>,
-) -> Result<Struct, String> {
+    bounds: Vec<Bound>,
+) -> Result<Struct, Diagnostic> {
     let mut fields = vec![];
     let mut methods = vec![];
     while lex.peek().is_newline() && lex.lookahead().is_tab() {
         lex.step();
         if !lex.next().is_identifier() {
-            return err(&lex, "field name after tab");
+            return err(lex, "field name after tab");
         }
         let field_name = lex.slice().to_string();
+        let field_start = lex.span().start;
         let (param_names, tok) = parse_params(lex);
-        if tok.is_colon() {
+        // `<name>: <type>` is a field, while a method has parameters or a `->`
+        let is_field = param_names.is_empty()
+            && (tok.is_semicolon() || tok.is_colon() && lex.peek().is_type());
+        if is_field {
+            if !lex.next().is_type() {
+                return err(lex, "field type after `;`");
+            }
+            fields.push(Field {
+                name: field_name,
+                r#type: parse_type(lex, &generics)?,
+                mutable: tok.is_semicolon(),
+                span: lex.span_from(field_start),
+            });
+        } else if tok.is_colon() {
             methods.push(parse_function(
                 lex,
                 field_name,
+                field_start,
                 param_names,
                 &generics,
                 true,
                 2,
             )?)
         } else if tok.is_type() {
-            fields.push((field_name, parse_type(lex, &generics)?));
+            fields.push(Field {
+                name: field_name,
+                r#type: parse_type(lex, &generics)?,
+                mutable: false,
+                span: lex.span_from(field_start),
+            });
         } else {
-            return err(&lex, "field type or `:` for method");
+            return err(lex, "field type or `:` for method");
         }
     }
+    let mut generics = generics;
+    for field in &fields {
+        field.r#type.generics(&mut generics);
+    }
+    remove_generics(&mut methods, &generics);
     Ok(Struct {
         name,
         generics,
+        bounds,
         fields,
         methods,
+        impl_methods: vec![],
+        span: lex.span_from(start),
+    })
+}
+
+fn parse_enum(
+    lex: &mut Lexer<Token>,
+    name: String,
+    start: usize,
+    generics: Vec<String>,
+    bounds: Vec<Bound>,
+) -> Result<Enum, Diagnostic> {
+    let mut variants = vec![];
+    while lex.peek().is_newline() && lex.lookahead().is_tab() {
+        lex.step();
+        if lex.next() != Some(Ok(Token::Type)) {
+            return err(lex, "variant name after tab");
+        }
+        let variant_name = lex.slice().to_string();
+        let mut types = vec![];
+        while lex.peek().is_type() {
+            lex.next();
+            types.push(parse_type(lex, &generics)?);
+        }
+        if !types.is_empty() {
+            variants.push(Variant::Tuple(variant_name, types));
+            continue;
+        }
+        let mut fields = vec![];
+        loop {
+            let mut line = lex.clone();
+            line.step_before();
+            if line.skip_indents().0 != 2 {
+                break;
+            }
+            *lex = line;
+            if !lex.next().is_identifier() {
+                return err(lex, "field name of struct variant");
+            }
+            let field_name = lex.slice().to_string();
+            let field_start = lex.span().start;
+            if !lex.next().is_colon() {
+                return err(lex, "`:` after field name");
+            }
+            if !lex.next().is_type() {
+                return err(lex, "field type after `:`");
+            }
+            fields.push(Field {
+                name: field_name,
+                r#type: parse_type(lex, &generics)?,
+                mutable: false,
+                span: lex.span_from(field_start),
+            });
+        }
+        variants.push(if fields.is_empty() {
+            Variant::Unit(variant_name)
+        } else {
+            Variant::Struct(variant_name, fields)
+        });
+    }
+    let mut generics = generics;
+    for variant in &variants {
+        match variant {
+            Variant::Tuple(_, types) => types.iter().for_each(|t| t.generics(&mut generics)),
+            Variant::Struct(_, fields) => {
+                fields.iter().for_each(|f| f.r#type.generics(&mut generics))
+            }
+            Variant::Unit(_) => (),
+        }
+    }
+    Ok(Enum {
+        name,
+        generics,
+        bounds,
+        variants,
+        span: lex.span_from(start),
     })
 }
 
 fn parse_impl(
     lex: &mut Lexer<Token>,
     struct_name: String,
+    start: usize,
     generic_types: Vec<String>,
-) -> Result<Impl, String> {
+    bounds: Vec<Bound>,
+) -> Result<Impl, Diagnostic> {
     if !lex.next().is_type() {
         return err(lex, "trait name after `=>`");
     }
@@ -221,16 +522,18 @@ fn parse_impl(
     while lex.peek().is_newline() && lex.lookahead().is_tab() {
         lex.step();
         if !lex.next().is_identifier() {
-            return err(&lex, "method name after tab");
+            return err(lex, "method name after tab");
         }
         let method_name = lex.slice().to_string();
+        let method_start = lex.span().start;
         let (param_names, tok) = parse_params(lex);
         if !tok.is_colon() {
-            return err(&lex, "`:` for method");
+            return err(lex, "`:` for method");
         }
         methods.push(parse_function(
             lex,
             method_name,
+            method_start,
             param_names,
             &generic_types,
             true,
@@ -240,12 +543,15 @@ fn parse_impl(
     Ok(Impl {
         struct_name,
         trait_name,
+        trait_types: vec![],
         generic_types,
+        bounds,
         methods,
+        span: lex.span_from(start),
     })
 }
 
-fn parse_params(lex: &mut Lexer<Token>) -> (Vec<String>, Option<Result<Token, ()>>) {
+fn parse_params(lex: &mut Lexer<Token>) -> (Vec<String>, Option<Result<Token, LexError>>) {
     let mut param_names = vec![];
     let mut tok = lex.next();
     while tok.is_identifier() {
@@ -258,19 +564,22 @@ fn parse_params(lex: &mut Lexer<Token>) -> (Vec<String>, Option<Result<Token, ()
 fn parse_signature(
     lex: &mut Lexer<Token>,
     name: String,
+    start: usize,
     param_names: Vec<String>,
-    parent_generics: &Vec<String>,
+    parent_generics: &[String],
     is_method: bool,
-) -> Result<TypeSignature, String> {
+) -> Result<TypeSignature, Diagnostic> {
     let mut signature = TypeSignature {
         name,
         param_names,
         param_types: vec![],
         generics: vec![],
+        bounds: vec![],
         return_types: vec![],
         is_method,
+        span: lex.span_from(start),
     };
-    let available_generics = parent_generics.clone();
+    let available_generics = parent_generics.to_vec();
     let mut tok = lex.next();
     while tok.is_type() {
         signature
@@ -288,167 +597,655 @@ fn parse_signature(
                 .push(parse_type(lex, &available_generics)?);
         }
     }
+    if let Some(last) = signature
+        .return_types
+        .last()
+        .or(signature.param_types.last())
+    {
+        signature.span = signature.span.to(&last.span);
+    }
+    for r#type in signature.param_types.iter().chain(&signature.return_types) {
+        r#type.generics(&mut signature.generics);
+    }
+    signature
+        .generics
+        .retain(|name| !parent_generics.contains(name));
     Ok(signature)
 }
 
 fn parse_function(
     lex: &mut Lexer<Token>,
     name: String,
+    start: usize,
     param_names: Vec<String>,
-    parent_generics: &Vec<String>,
+    parent_generics: &[String],
     is_method: bool,
     indent: usize,
-) -> Result<Function, String> {
-    let mut func = Function {
-        signature: parse_signature(lex, name, param_names, parent_generics, is_method)?,
-        body: vec![],
+) -> Result<Function, Diagnostic> {
+    let signature = parse_signature(lex, name, start, param_names, parent_generics, is_method)?;
+    let body = parse_body(lex, indent);
+    Ok(Function {
+        span: body
+            .last()
+            .map_or(signature.span.clone(), |expr| signature.span.to(&expr.span)),
+        signature,
+        body,
+        equations: vec![],
+    })
+}
+
+/// Whether the tokens after a function name form an equation, such as `0 = 1`
+/// or `n <- n > 0 = n`, instead of a signature or variable declaration
+fn is_equation(lex: &Lexer<Token>) -> bool {
+    let mut scan = lex.clone();
+    loop {
+        match scan.next() {
+            Some(Ok(Token::Assign | Token::If)) => return true,
+            None | Some(Ok(Token::Colon | Token::Semicolon | Token::Newline)) => return false,
+            _ => (),
+        }
+    }
+}
+
+fn parse_equation(lex: &mut Lexer<Token>) -> Result<Equation, Diagnostic> {
+    let start = lex.span().start;
+    let mut parameters_list = vec![];
+    while !matches!(lex.peek(), Some(Ok(Token::Assign | Token::If))) {
+        parameters_list.push(parse_parameter(lex)?);
+    }
+    let guard = if lex.peek().is_if() {
+        lex.next();
+        // skip assignments so the guard stops before the equation's `=`
+        let lhs = parse_primary(lex)?;
+        Some(parse_binary_expression(lex, lhs, 2)?)
+    } else {
+        None
     };
-    let mut indents = indent;
-    let mut tok;
-    while indents >= indent {
-        // TODO: need to handle indent level when returning, such as inside another function
-        (indents, tok) = lex.skip_indents();
-        if tok.is_newline() {
-            lex.next();
-            continue;
+    if !lex.next().is_assign() {
+        return err(lex, "`=` after equation patterns");
+    }
+    let body = if lex.peek().is_newline() {
+        parse_body(lex, 1)
+    } else {
+        vec![parse_expression(lex)?]
+    };
+    Ok(Equation {
+        parameters_list,
+        guard,
+        body,
+        span: lex.span_from(start),
+    })
+}
+
+/// Parses every line indented exactly `indent` tabs, stopping before the newline
+/// of the last expression so the caller sees the block as a single expression.
+fn parse_body(lex: &mut Lexer<Token>, indent: usize) -> Vec<Expr> {
+    let body = parse_lines(lex, indent, parse_expression);
+    if let Some(i) = body
+        .iter()
+        .position(|expr| matches!(expr.kind, ExprKind::Break(_) | ExprKind::Continue))
+        && let Some(next) = body.get(i + 1)
+    {
+        lex.extras.diagnostics.push(
+            Diagnostic::warning("unreachable statement")
+                .with_code("SLW0001")
+                .with_primary(next.span.clone(), "unreachable statement")
+                .with_secondary(
+                    body[i].span.clone(),
+                    "any code following this expression is unreachable",
+                ),
+        );
+    }
+    body
+}
+
+/// Same as [`parse_body`] but each line is parsed with `parse_line`
+fn parse_lines<T>(
+    lex: &mut Lexer<Token>,
+    indent: usize,
+    mut parse_line: impl FnMut(&mut Lexer<Token>) -> Result<T, Diagnostic>,
+) -> Vec<T> {
+    let mut lines = vec![];
+    let mut line = lex.clone();
+    loop {
+        if line.peek().is_newline() {
+            line.next();
         }
-        if indents > indent {
-            return err(lex, "newline after empty line");
+        let (indents, tok) = line.skip_indents();
+        match tok {
+            None => break,
+            Some(Ok(Token::Newline)) => continue,
+            Some(Ok(Token::Comment)) => {
+                line.next();
+                continue;
+            }
+            _ if indents < indent => break,
+            _ => (),
         }
-        func.body.push(parse_expression(lex)?);
-        if !lex.next().is_newline() {
-            return err(lex, "newline after expression");
+        *lex = line;
+        let parsed = if indents > indent {
+            err(lex, &format!("{} level(s) of indentation", indent))
+        } else {
+            parse_line(lex).and_then(|parsed| end_line(lex).map(|()| parsed))
+        };
+        match parsed {
+            Ok(parsed) => lines.push(parsed),
+            Err(diagnostic) => {
+                lex.extras.diagnostics.push(diagnostic);
+                recover(lex, indent);
+            }
         }
+        line = lex.clone();
     }
-    Ok(func)
+    lines
 }
 
-fn parse_generic_types(lex: &mut Lexer<Token>) -> Result<Vec<String>, String> {
+/// Skips a trailing comment, expecting the line to end afterwards
+fn end_line(lex: &mut Lexer<Token>) -> Result<(), Diagnostic> {
+    if lex.peek() == Some(Ok(Token::Comment)) {
+        lex.next();
+    }
+    if !lex.peek().is_newline() && lex.peek().is_some() {
+        lex.next();
+        return err(lex, "newline after expression");
+    }
+    Ok(())
+}
+
+/// Moves the lexer to the end of the line it failed on, along with the lines
+/// after it indented deeper than `indent`, so that parsing can resume at the
+/// next statement or, with an `indent` of 0, the next top-level item
+fn recover(lex: &mut Lexer<Token>, indent: usize) {
+    let source = lex.source();
+    let start = lex.span().start.min(source.len());
+    let mut end = source[start..]
+        .find('\n')
+        .map_or(source.len(), |i| start + i);
+    while end < source.len() {
+        let line = source[end + 1..].split('\n').next().unwrap_or_default();
+        let tabs = line.chars().take_while(|c| *c == '\t').count();
+        let rest = line[tabs..].trim_start_matches(' ');
+        if tabs <= indent && !rest.is_empty() && !rest.starts_with('\\') {
+            break;
+        }
+        end += line.len() + 1;
+    }
+    // Bumping the lexer itself keeps its span at the real offset for the next
+    // recovery, and never moving it backwards guarantees that parsing ends
+    lex.bump(end.saturating_sub(lex.span().end));
+}
+
+/// Parses the generic types after `<`, each of which may have bounds such as `T: Animal + Display`
+fn parse_generic_types(lex: &mut Lexer<Token>) -> Result<(Vec<String>, Vec<Bound>), Diagnostic> {
     let mut generic_types = vec![];
+    let mut bounds = vec![];
     let mut tok = lex.next();
     while tok.is_type() {
-        generic_types.push(lex.slice().to_string());
+        if tok == Some(Ok(Token::Tick)) {
+            lex.next();
+        }
+        let name = lex.slice().to_string();
+        let name_span = lex.token_span();
+        generic_types.push(name.clone());
         tok = lex.next();
+        if !tok.is_colon() {
+            continue;
+        }
+        let mut traits = vec![];
+        loop {
+            if !lex.next().is_type() {
+                return err(lex, "trait name in bound");
+            }
+            let r#trait = parse_type(lex, &generic_types)?;
+            if let TypeKind::Named(name, _) = &r#trait.kind
+                && matches!(name.as_str(), "Display" | "Debug")
+            {
+                use_std(lex, name);
+            }
+            traits.push(r#trait);
+            tok = lex.next();
+            if tok != Some(Ok(Token::Plus)) {
+                break;
+            }
+        }
+        bounds.push(Bound {
+            generic: name,
+            span: name_span.to(&traits[traits.len() - 1].span),
+            traits,
+        });
     }
     if tok != Some(Ok(Token::GreaterThan)) {
         return err(lex, "`>` after generic type declaration");
     }
-    Ok(generic_types)
+    Ok((generic_types, bounds))
 }
 
 fn parse_assignment(
     lex: &mut Lexer<Token>,
-    name: String,
+    pattern: Pattern,
     assign_type: AssignType,
     mutable: bool,
-) -> Result<Expr, String> {
+) -> Result<Expr, Diagnostic> {
     let mut type_hint = None;
     let mut tok = lex.next();
     if tok.is_type() {
-        type_hint = Some(parse_type(lex, &vec![])?);
+        type_hint = Some(parse_type(lex, &[])?);
         tok = lex.next();
     }
     if !tok.is_assign() {
         return err(lex, "`=` after type or `:` or `;`");
     };
-    Ok(Expr::Assign {
-        name,
-        assign_type,
-        mutable,
-        value: Box::new(parse_expression(lex)?),
-        type_hint,
-    })
+    let value = parse_expression(lex)?;
+    let span = pattern.span.to(&value.span);
+    Ok(Expr::new(
+        ExprKind::Assign {
+            pattern,
+            assign_type,
+            mutable,
+            value: Box::new(value),
+            type_hint,
+        },
+        span,
+    ))
 }
 
-fn parse_expression(lex: &mut Lexer<Token>) -> Result<Expr, String> {
+fn parse_expression(lex: &mut Lexer<Token>) -> Result<Expr, Diagnostic> {
     let lhs = if lex.peek() == Some(Ok(Token::Dot)) {
-        Expr::This
+        let mut dot = lex.clone();
+        dot.next();
+        Expr::new(ExprKind::This, dot.token_span())
     } else {
         parse_primary(lex)?
     };
     let expr = parse_binary_expression(lex, lhs, 1)?;
-    parse_ternary_expression(lex, expr)
+    let expr = parse_ternary_expression(lex, expr)?;
+    if lex.peek() == Some(Ok(Token::Eroteme)) {
+        lex.next();
+        return parse_match(lex, expr);
+    }
+    Ok(expr)
 }
 
-fn parse_primary(lex: &mut Lexer<Token>) -> Result<Expr, String> {
-    let mut result = if let Some(Ok(tok)) = lex.next() {
-        match tok {
-            Token::Identifier => {
-                let name = lex.slice().to_string();
-                match lex.peek() {
-                    Some(Ok(Token::Colon)) => {
-                        lex.next();
-                        parse_assignment(lex, name, AssignType::Normal, false)?
-                    }
-                    Some(Ok(Token::Semicolon)) => {
-                        lex.next();
-                        parse_assignment(lex, name, AssignType::Normal, true)?
-                    }
-                    _ => parse_identifier(lex, name)?,
+/// Parses the indented arms of `<expr> ?`, each being
+/// `<pattern> [<- <guard>] => <expr>` or `=>` followed by an indented body
+fn parse_match(lex: &mut Lexer<Token>, subject: Expr) -> Result<Expr, Diagnostic> {
+    let indent = lex.indent_level() + 1;
+    if !lex.peek().is_newline() {
+        lex.next();
+        return err(lex, "newline after match `?`");
+    }
+    let errors = lex.extras.diagnostics.len();
+    let arms = parse_lines(lex, indent, |lex| {
+        let pattern = parse_parameter(lex)?;
+        let start = pattern.span.range.start;
+        let guard = if lex.peek().is_if() {
+            lex.next();
+            Some(parse_expression(lex)?)
+        } else {
+            None
+        };
+        if lex.next() != Some(Ok(Token::FatArrow)) {
+            return err(lex, "`=>` after match pattern");
+        }
+        let body = if lex.peek().is_newline() {
+            parse_body(lex, indent + 1)
+        } else {
+            vec![parse_expression(lex)?]
+        };
+        Ok(MatchArm {
+            pattern,
+            guard,
+            body,
+            span: lex.span_from(start),
+        })
+    });
+    // Arms that failed to parse were already reported
+    if arms.is_empty() && lex.extras.diagnostics.len() == errors {
+        return err(lex, "at least one indented match arm");
+    }
+    let span = arms
+        .last()
+        .map_or(subject.span.clone(), |last| subject.span.to(&last.span));
+    Ok(Expr::new(
+        ExprKind::Match {
+            subject: Box::new(subject),
+            arms,
+        },
+        span,
+    ))
+}
+
+fn parse_primary(lex: &mut Lexer<Token>) -> Result<Expr, Diagnostic> {
+    let Some(Ok(tok)) = lex.next() else {
+        return err(lex, "primary expression");
+    };
+    let start = lex.span().start;
+    let kind = match tok {
+        Token::Identifier | Token::Underscore if let Some(pattern) = parse_binding(lex) => {
+            match lex.next() {
+                Some(Ok(Token::Colon)) => {
+                    parse_assignment(lex, pattern, AssignType::Normal, false)?.kind
                 }
+                Some(Ok(Token::Semicolon)) => {
+                    parse_assignment(lex, pattern, AssignType::Normal, true)?.kind
+                }
+                _ => parse_for(lex, pattern)?,
             }
-            Token::ParamIdentifier => {
-                let name = lex.slice()[1..].to_string();
-                Expr::AnonParam(Box::new(parse_identifier(lex, name)?))
-            }
-            Token::Underscore => Expr::AnonParam(Box::new(Expr::None)),
-            Token::Float | Token::Integer | Token::String => parse_literal(lex, &tok)?,
-            Token::Star => {
-                let expr = parse_expression(lex)?;
-                Expr::Reference(Box::new(expr))
+        }
+        Token::Identifier => {
+            let name = lex.slice().to_string();
+            parse_identifier(lex, name)?
+        }
+        Token::ConstIdentifier => ExprKind::Variable(lex.slice().to_string()),
+        // a negative number rather than a subtraction, such as `-1`
+        Token::Minus
+            if lex.is_adjacent()
+                && matches!(lex.peek(), Some(Ok(Token::Integer | Token::Float))) =>
+        {
+            let Some(Ok(tok)) = lex.next() else {
+                unreachable!("the next token was peeked as a number");
+            };
+            match parse_literal(lex, &tok)? {
+                ExprKind::Literal(Literal::Integer(i)) => ExprKind::Literal(Literal::Integer(-i)),
+                // only `i128::MIN` is both negative and above `i128::MAX` once negated
+                ExprKind::Literal(Literal::Unsigned(u)) => match 0i128.checked_sub_unsigned(u) {
+                    Some(i) => ExprKind::Literal(Literal::Integer(i)),
+                    None => {
+                        return Err(Diagnostic::error("integer literal is too small")
+                            .with_code("SL0026")
+                            .with_primary(lex.span_from(start), "too small for any number type")
+                            .with_note(format!(
+                                "the smallest integer literal is `{}`",
+                                i128::MIN
+                            )));
+                    }
+                },
+                ExprKind::Literal(Literal::Float(f)) => ExprKind::Literal(Literal::Float(-f)),
+                kind => kind,
             }
-            Token::LeftParen => {
-                let expr = parse_expression(lex)?;
-                if lex.next() != Some(Ok(Token::RightParen)) {
-                    err(lex, "closing parenthesis `)`")?
-                } else {
-                    expr
-                }
+        }
+        Token::ParamIdentifier => {
+            let name = lex.slice()[1..].to_string();
+            let kind = parse_identifier(lex, name)?;
+            ExprKind::AnonParam(Box::new(Expr::new(kind, lex.span_from(start))))
+        }
+        Token::Underscore => {
+            ExprKind::AnonParam(Box::new(Expr::new(ExprKind::None, lex.token_span())))
+        }
+        Token::Type => {
+            let enum_name = lex.slice().to_string();
+            if lex.next() != Some(Ok(Token::Dot)) || lex.next() != Some(Ok(Token::Type)) {
+                return err(lex, "`.` and variant name after enum name");
             }
-            Token::LeftBracket => {
-                let mut elements = vec![];
-                while lex.peek() != Some(Ok(Token::RightBracket)) {
-                    elements.push(parse_expression(lex)?);
+            let variant = lex.slice().to_string();
+            let mut args = vec![];
+            if lex.peek() == Some(Ok(Token::LeftParen)) && lex.is_adjacent() {
+                lex.next();
+                while !lex.peek().is_right_paren() {
+                    args.push(parse_expression(lex)?);
                 }
                 lex.next();
-                Expr::List(elements)
             }
-            Token::LeftSome => {
-                if lex.peek().is_right_paren() {
+            ExprKind::Variant {
+                enum_name,
+                variant,
+                args,
+            }
+        }
+        Token::Float | Token::Integer | Token::String => parse_literal(lex, &tok)?,
+        Token::Star => {
+            let expr = parse_expression(lex)?;
+            ExprKind::Reference(Box::new(expr))
+        }
+        Token::LeftParen => {
+            let mut elements = vec![];
+            while !lex.peek().is_right_paren() {
+                if lex.peek().is_none() {
                     lex.next();
-                    Expr::None
-                } else {
-                    let inner = parse_expression(lex)?;
-                    if !lex.next().is_right_some() {
-                        err(lex, "closing `|)` after Some inner expression")?
-                    } else {
-                        Expr::Some(Box::new(inner))
-                    }
+                    return err(lex, "closing parenthesis `)`");
                 }
+                elements.push(parse_expression(lex)?);
+            }
+            lex.next();
+            if elements.len() == 1 {
+                elements.pop().unwrap().kind
+            } else {
+                ExprKind::Tuple(elements)
             }
-            Token::Comment => parse_primary(lex)?,
-            _ => err(lex, "primary expression")?,
         }
-    } else {
-        err(lex, "primary expression")?
+        Token::LeftBracket => {
+            let mut elements = vec![];
+            while lex.peek() != Some(Ok(Token::RightBracket)) {
+                elements.push(parse_expression(lex)?);
+            }
+            lex.next();
+            ExprKind::List(elements)
+        }
+        Token::LeftBrace => parse_braces(lex)?,
+        Token::LeftSome => {
+            if lex.peek().is_right_paren() {
+                lex.next();
+                ExprKind::None
+            } else {
+                let inner = parse_expression(lex)?;
+                if !lex.next().is_right_some() {
+                    return err(lex, "closing `|)` after Some inner expression");
+                }
+                ExprKind::Some(Box::new(inner))
+            }
+        }
+        Token::At => parse_loop(lex)?,
+        Token::Break => {
+            let Some(&is_infinite) = lex.extras.loops.last() else {
+                return outside_loop(lex);
+            };
+            if ends_statement(lex.peek()) {
+                ExprKind::Break(None)
+            } else if !is_infinite {
+                let value = parse_expression(lex)?;
+                return Err(Diagnostic::error("only `@` loops can `break` with a value")
+                    .with_code("SL0003")
+                    .with_primary(value.span, "unexpected value")
+                    .with_note("`for` and `@ <-` loops always evaluate to `()`"));
+            } else {
+                ExprKind::Break(Some(Box::new(parse_expression(lex)?)))
+            }
+        }
+        Token::Continue => {
+            if lex.extras.loops.is_empty() {
+                return outside_loop(lex);
+            }
+            ExprKind::Continue
+        }
+        Token::Comment => return parse_primary(lex),
+        _ => return err(lex, "primary expression"),
     };
+    let result = Expr::new(kind, lex.span_from(start));
+    parse_postfix(lex, result)
+}
+
+/// Parses the `{<key>}` entries, `!` and `'` that follow a primary expression
+fn parse_postfix(lex: &mut Lexer<Token>, mut result: Expr) -> Result<Expr, Diagnostic> {
+    let start = result.span.range.start;
     loop {
-        match lex.peek() {
+        let kind = match lex.peek() {
+            Some(Ok(Token::LeftBrace)) if lex.is_adjacent() => {
+                lex.next();
+                let key = parse_expression(lex)?;
+                if lex.next() != Some(Ok(Token::RightBrace)) {
+                    return err(lex, "closing brace `}` after map key");
+                }
+                ExprKind::Entry {
+                    map: Box::new(result),
+                    key: Box::new(key),
+                }
+            }
             Some(Ok(Token::Bang)) => {
                 lex.next();
-                result = Expr::Ok(Box::new(result));
+                ExprKind::Ok(Box::new(result))
             }
             Some(Ok(Token::Tick)) => {
                 lex.next();
-                result = Expr::Err(Box::new(result));
+                ExprKind::Err(Box::new(result))
             }
             _ => break,
+        };
+        result = Expr::new(kind, lex.span_from(start));
+    }
+    Ok(result)
+}
+
+/// Parses a map `{<key> => <value> ...}` or a set `{<item> ...}` literal,
+/// where `{=>}` is an empty map and `{}` is an empty set
+fn parse_braces(lex: &mut Lexer<Token>) -> Result<ExprKind, Diagnostic> {
+    let result = match lex.peek() {
+        Some(Ok(Token::FatArrow)) => {
+            lex.next();
+            ExprKind::Map(vec![])
+        }
+        Some(Ok(Token::RightBrace)) => ExprKind::Set(vec![]),
+        _ => {
+            let first = parse_expression(lex)?;
+            if lex.peek() == Some(Ok(Token::FatArrow)) {
+                lex.next();
+                let mut entries = vec![(first, parse_expression(lex)?)];
+                while lex.peek() != Some(Ok(Token::RightBrace)) {
+                    let key = parse_expression(lex)?;
+                    if lex.next() != Some(Ok(Token::FatArrow)) {
+                        return err(lex, "`=>` after map key");
+                    }
+                    entries.push((key, parse_expression(lex)?));
+                }
+                ExprKind::Map(entries)
+            } else {
+                let mut items = vec![first];
+                while lex.peek() != Some(Ok(Token::RightBrace)) {
+                    items.push(parse_expression(lex)?);
+                }
+                ExprKind::Set(items)
+            }
         }
+    };
+    if lex.next() != Some(Ok(Token::RightBrace)) {
+        return err(lex, "closing brace `}`");
     }
+    use_std(
+        lex,
+        if matches!(result, ExprKind::Map(_)) {
+            "HashMap"
+        } else {
+            "HashSet"
+        },
+    );
     Ok(result)
 }
 
-fn parse_num_type_bits(lex: &Lexer<Token>, bits: &str) -> Result<u8, String> {
+/// Marks `item` from `std` as needed by the generated code
+fn use_std(lex: &mut Lexer<Token>, item: &str) {
+    if !lex.extras.std_items.iter().any(|i| i == item) {
+        lex.extras.std_items.push(item.to_string());
+    }
+}
+
+/// Collects the names bound by the statement starting at the current token,
+/// such as `a` in `a := 1` or `k v` in `k v @ pairs`, leaving the lexer before
+/// the `:`, `;` or `@` marker. Returns `None` if the statement is not a binding.
+fn parse_binding(lex: &mut Lexer<Token>) -> Option<Pattern> {
+    let mut scan = lex.clone();
+    let mut patterns = vec![];
+    loop {
+        let kind = match scan.slice() {
+            "_" => PatternKind::Wildcard,
+            name => PatternKind::Variable(name.to_string()),
+        };
+        patterns.push(Pattern::new(kind, scan.token_span()));
+        match scan.peek() {
+            Some(Ok(Token::Identifier | Token::Underscore)) => {
+                scan.next();
+            }
+            Some(Ok(Token::Colon | Token::At)) => break,
+            Some(Ok(Token::Semicolon))
+                if matches!(scan.lookahead2(), Some(Ok(Token::Assign | Token::Type))) =>
+            {
+                break;
+            }
+            _ => return None,
+        }
+    }
+    *lex = scan;
+    Some(if patterns.len() == 1 {
+        patterns.pop().unwrap()
+    } else {
+        let span = patterns[0].span.to(&patterns[patterns.len() - 1].span);
+        Pattern::new(PatternKind::Tuple(patterns), span)
+    })
+}
+
+fn parse_for(lex: &mut Lexer<Token>, pattern: Pattern) -> Result<ExprKind, Diagnostic> {
+    let indent = lex.indent_level() + 1;
+    let iterable = parse_expression(lex)?;
+    if !lex.peek().is_newline() {
+        lex.next();
+        return err(lex, "newline after for loop iterable");
+    }
+    Ok(ExprKind::For {
+        pattern,
+        iterable: Box::new(iterable),
+        body: parse_loop_body(lex, indent, false),
+    })
+}
+
+fn parse_loop(lex: &mut Lexer<Token>) -> Result<ExprKind, Diagnostic> {
+    let indent = lex.indent_level() + 1;
+    let condition = if lex.peek().is_if() {
+        lex.next();
+        Some(parse_expression(lex)?)
+    } else {
+        None
+    };
+    if !lex.peek().is_newline() {
+        lex.next();
+        return err(lex, "newline after loop header");
+    }
+    Ok(match condition {
+        Some(condition) => ExprKind::While {
+            condition: Box::new(condition),
+            body: parse_loop_body(lex, indent, false),
+        },
+        None => ExprKind::Loop(parse_loop_body(lex, indent, true)),
+    })
+}
+
+fn parse_loop_body(lex: &mut Lexer<Token>, indent: usize, is_infinite: bool) -> Vec<Expr> {
+    lex.extras.loops.push(is_infinite);
+    let body = parse_body(lex, indent);
+    lex.extras.loops.pop();
+    body
+}
+
+/// Whether `tok` cannot start the value of a `break`
+fn ends_statement(tok: Option<Result<Token, LexError>>) -> bool {
+    matches!(
+        tok,
+        None | Some(Ok(Token::Newline
+            | Token::Comment
+            | Token::If
+            | Token::Semicolon
+            | Token::RightParen))
+    )
+}
+
+/// Whether `name` is a number type such as `N8` or `Z64` for the given `prefix`
+/// Types that need no declaration, besides generic ones
+const BUILTIN_TYPES: [&str; 13] = [
+    "String", "N8", "N16", "N32", "N64", "N128", "Z8", "Z16", "Z32", "Z64", "Z128", "R32", "R64",
+];
+
+fn is_num_type(name: &str, prefix: char) -> bool {
+    name.starts_with(prefix) && name[1..].chars().all(|c| c.is_ascii_digit())
+}
+
+fn parse_num_type_bits(lex: &Lexer<Token>, bits: &str) -> Result<u8, Diagnostic> {
     if let Ok(bits) = bits.parse::<u8>()
         && [8, 16, 32, 64, 128].contains(&bits)
     {
@@ -458,8 +1255,9 @@ fn parse_num_type_bits(lex: &Lexer<Token>, bits: &str) -> Result<u8, String> {
     }
 }
 
-fn parse_type(lex: &mut Lexer<Token>, generics: &Vec<String>) -> Result<Type, String> {
-    let mut result = match lex.slice() {
+fn parse_type(lex: &mut Lexer<Token>, generics: &[String]) -> Result<Type, Diagnostic> {
+    let start = lex.span().start;
+    let kind = match lex.slice() {
         "(" => {
             let mut arg_types = vec![];
             let mut return_types = vec![];
@@ -468,18 +1266,22 @@ fn parse_type(lex: &mut Lexer<Token>, generics: &Vec<String>) -> Result<Type, St
                 arg_types.push(parse_type(lex, generics)?);
                 tok = lex.next();
             }
-            if !tok.is_arrow() {
-                return err(lex, "`->` after arg types");
-            }
-            tok = lex.next();
-            while tok.is_type() {
-                return_types.push(parse_type(lex, generics)?);
+            if tok.is_right_paren() {
+                TypeKind::Tuple(arg_types)
+            } else {
+                if !tok.is_arrow() {
+                    return err(lex, "`->` or `)` after types");
+                }
                 tok = lex.next();
+                while tok.is_type() {
+                    return_types.push(parse_type(lex, generics)?);
+                    tok = lex.next();
+                }
+                if !tok.is_right_paren() {
+                    return err(lex, "`)` after return types");
+                }
+                TypeKind::Closure(arg_types, return_types)
             }
-            if !tok.is_right_paren() {
-                return err(lex, "`)` after return types");
-            }
-            Type::Closure(arg_types, return_types)
         }
         "[" => {
             if !lex.next().is_type() {
@@ -489,48 +1291,117 @@ fn parse_type(lex: &mut Lexer<Token>, generics: &Vec<String>) -> Result<Type, St
             if lex.next() != Some(Ok(Token::RightBracket)) {
                 return err(lex, "closing bracket `]`");
             }
-            Type::List(Box::new(inner_type))
+            TypeKind::List(Box::new(inner_type))
+        }
+        "{" => {
+            if !lex.next().is_type() {
+                return err(lex, "type after `{`");
+            }
+            let key_type = parse_type(lex, generics)?;
+            match lex.next() {
+                Some(Ok(Token::RightBrace)) => TypeKind::Set(Box::new(key_type)),
+                Some(Ok(Token::FatArrow)) => {
+                    if !lex.next().is_type() {
+                        return err(lex, "value type after `=>`");
+                    }
+                    let value_type = parse_type(lex, generics)?;
+                    if lex.next() != Some(Ok(Token::RightBrace)) {
+                        return err(lex, "closing brace `}` for map type");
+                    }
+                    TypeKind::Map(Box::new(key_type), Box::new(value_type))
+                }
+                _ => return err(lex, "`=>` or `}` after type"),
+            }
         }
         "*" => {
             if !lex.next().is_type() {
                 return err(lex, "type after `*`");
             }
             let inner_type = parse_type(lex, generics)?;
-            Type::Reference(Box::new(inner_type))
+            TypeKind::Reference(Box::new(inner_type))
         }
         _ => {
             let result = match lex.slice() {
-                s if s.starts_with('N') => Type::Unsigned(parse_num_type_bits(lex, &s[1..])?),
-                s if s.starts_with('Z') => Type::Integer(parse_num_type_bits(lex, &s[1..])?),
-                s if s.starts_with('R') => Type::Float(parse_num_type_bits(lex, &s[1..])?),
-                "String" => Type::String,
-                tok if generics.contains(&tok.to_string()) => Type::Generic(tok.to_string()),
+                s if is_num_type(s, 'N') => TypeKind::Unsigned(parse_num_type_bits(lex, &s[1..])?),
+                s if is_num_type(s, 'Z') => TypeKind::Integer(parse_num_type_bits(lex, &s[1..])?),
+                s if is_num_type(s, 'R') => TypeKind::Float(parse_num_type_bits(lex, &s[1..])?),
+                "String" => TypeKind::String,
+                tok if generics.contains(&tok.to_string()) => TypeKind::Generic(tok.to_string()),
+                // generic types need no declaration when written as `t` or `'T`
+                name if name.starts_with(char::is_lowercase) && name.chars().count() == 1 => {
+                    TypeKind::Generic(name.to_string())
+                }
+                name if name.starts_with(char::is_lowercase) => {
+                    return Err(unknown_type(lex, name));
+                }
+                "'" => {
+                    if !matches!(
+                        lex.next(),
+                        Some(Ok(Token::Type | Token::ConstIdentifier | Token::Identifier))
+                    ) {
+                        return err(lex, "generic type name after `'`");
+                    }
+                    TypeKind::Generic(lex.slice().to_string())
+                }
+                name if name.starts_with(char::is_uppercase) => {
+                    let name = name.to_string();
+                    let mut args = vec![];
+                    if lex.peek() == Some(Ok(Token::LessThan)) && lex.is_adjacent() {
+                        lex.next();
+                        loop {
+                            match lex.next() {
+                                Some(Ok(Token::GreaterThan)) => break,
+                                Some(Ok(Token::RightList)) => {
+                                    lex.split(1);
+                                    break;
+                                }
+                                tok if tok.is_type() => args.push(parse_type(lex, generics)?),
+                                _ => return err(lex, "type argument or `>`"),
+                            }
+                        }
+                    }
+                    TypeKind::Named(name, args)
+                }
                 _ => return err(lex, "type"),
             };
-            if lex.peek() == Some(Ok(Token::LeftBracket)) {
+            let element_span = lex.span_from(start);
+            if lex.peek() == Some(Ok(Token::LeftBracket)) && lex.is_adjacent() {
                 lex.next();
                 if !lex.next().is_integer() {
                     return err(lex, "size of array after `[`");
                 }
-                let size = lex.slice().parse::<usize>().unwrap();
+                let Ok(size) = lex.slice().parse::<usize>() else {
+                    return Err(Diagnostic::error("array size is too large")
+                        .with_code("SL0026")
+                        .with_primary(lex.token_span(), "too large for an array size")
+                        .with_note(format!("the largest array size is `{}`", usize::MAX)));
+                };
                 if lex.next() != Some(Ok(Token::RightBracket)) {
                     return err(lex, "closing bracket `]` for array");
                 }
-                return Ok(Type::Array(Box::new(result), size));
+                return Ok(Type::new(
+                    TypeKind::Array(Box::new(Type::new(result, element_span)), size),
+                    lex.span_from(start),
+                ));
             }
             result
         }
     };
+    let mut result = Type::new(kind, lex.span_from(start));
     loop {
         match lex.peek() {
             Some(Ok(Token::Eroteme)) => {
                 lex.next();
-                result = Type::Option(Box::new(result));
+                result = Type::new(TypeKind::Option(Box::new(result)), lex.span_from(start));
             }
             Some(Ok(Token::Bang)) => {
                 lex.next();
                 lex.next();
-                result = Type::Result(Box::new(result), Box::new(parse_type(lex, generics)?));
+                let err_type = parse_type(lex, generics)?;
+                result = Type::new(
+                    TypeKind::Result(Box::new(result), Box::new(err_type)),
+                    lex.span_from(start),
+                );
             }
             _ => break,
         }
@@ -538,56 +1409,105 @@ fn parse_type(lex: &mut Lexer<Token>, generics: &Vec<String>) -> Result<Type, St
     Ok(result)
 }
 
-fn parse_parameter(lex: &mut Lexer<Token>) -> Result<Pattern, String> {
+fn parse_parameter(lex: &mut Lexer<Token>) -> Result<Pattern, Diagnostic> {
     let Some(Ok(tok)) = lex.next() else {
         return err(lex, "literal function parameter");
     };
-    match tok {
+    let start = lex.span().start;
+    let kind = match tok {
         Token::Float => {
             let value = lex.slice().parse::<f64>().unwrap();
-            Ok(Pattern::Literal(Literal::Float(value)))
-        }
-        Token::Integer => {
-            let value = lex.slice().parse::<i64>().unwrap();
-            Ok(Pattern::Literal(Literal::Integer(value)))
+            PatternKind::Literal(Literal::Float(value))
         }
+        Token::Integer => PatternKind::Literal(parse_integer(lex)?),
         Token::String => {
             let value = lex.slice().trim_matches('"').to_string();
-            Ok(Pattern::Literal(Literal::String(value)))
+            PatternKind::Literal(Literal::String(value))
+        }
+        Token::Identifier => PatternKind::Variable(lex.slice().to_string()),
+        Token::Underscore => PatternKind::Wildcard,
+        Token::Type => {
+            let enum_name = lex.slice().to_string();
+            if lex.next() != Some(Ok(Token::Dot)) || lex.next() != Some(Ok(Token::Type)) {
+                return err(lex, "`.` and variant name after enum name");
+            }
+            let variant = lex.slice().to_string();
+            match lex.peek() {
+                Some(Ok(Token::LeftBrace)) if lex.is_adjacent() => {
+                    lex.next();
+                    let mut fields = vec![];
+                    while lex.peek().is_identifier() {
+                        lex.next();
+                        fields.push(lex.slice().to_string());
+                    }
+                    if lex.next() != Some(Ok(Token::RightBrace)) {
+                        return err(lex, "field name or `}` in variant pattern");
+                    }
+                    PatternKind::StructVariant(enum_name, variant, fields)
+                }
+                Some(Ok(Token::LeftParen)) if lex.is_adjacent() => {
+                    lex.next();
+                    let mut elements = vec![];
+                    while !lex.peek().is_right_paren() {
+                        elements.push(parse_parameter(lex)?);
+                    }
+                    lex.next();
+                    PatternKind::Variant(enum_name, variant, elements)
+                }
+                _ => PatternKind::Variant(enum_name, variant, vec![]),
+            }
         }
-        Token::Identifier => Ok(Pattern::Variable(lex.slice().to_string())),
-        Token::Underscore => Ok(Pattern::Wildcard),
         Token::LeftBracket => {
             let mut elements = vec![];
             while lex.peek() != Some(Ok(Token::RightBracket)) {
                 elements.push(parse_parameter(lex)?);
             }
             lex.next();
-            Ok(Pattern::List(elements))
+            PatternKind::List(elements)
         }
-        _ => err(lex, "literal function parameter"),
-    }
+        Token::LeftParen => {
+            let mut elements = vec![];
+            while !lex.peek().is_right_paren() {
+                elements.push(parse_parameter(lex)?);
+            }
+            lex.next();
+            PatternKind::Tuple(elements)
+        }
+        _ => return err(lex, "literal function parameter"),
+    };
+    Ok(Pattern::new(kind, lex.span_from(start)))
 }
 
-fn parse_literal(lex: &mut Lexer<Token>, tok: &Token) -> Result<Expr, String> {
+fn parse_literal(lex: &mut Lexer<Token>, tok: &Token) -> Result<ExprKind, Diagnostic> {
     match tok {
         Token::Float => {
             let value = lex.slice().parse::<f64>().unwrap();
-            Ok(Expr::Literal(Literal::Float(value)))
-        }
-        Token::Integer => {
-            let value = lex.slice().parse::<i64>().unwrap();
-            Ok(Expr::Literal(Literal::Integer(value)))
+            Ok(ExprKind::Literal(Literal::Float(value)))
         }
+        Token::Integer => Ok(ExprKind::Literal(parse_integer(lex)?)),
         Token::String => {
             let value = lex.slice().trim_matches('"').to_string();
-            Ok(Expr::Literal(Literal::String(value)))
+            Ok(ExprKind::Literal(Literal::String(value)))
         }
         _ => err(lex, "literal expression"),
     }
 }
 
-fn parse_identifier(lex: &mut Lexer<Token>, name: String) -> Result<Expr, String> {
+/// Parses the current integer token, which may be too large even for `N128`
+fn parse_integer(lex: &Lexer<Token>) -> Result<Literal, Diagnostic> {
+    if let Ok(i) = lex.slice().parse::<i128>() {
+        Ok(Literal::Integer(i))
+    } else if let Ok(u) = lex.slice().parse::<u128>() {
+        Ok(Literal::Unsigned(u))
+    } else {
+        Err(Diagnostic::error("integer literal is too large")
+            .with_code("SL0026")
+            .with_primary(lex.token_span(), "too large for any number type")
+            .with_note(format!("the largest integer literal is `{}`", u128::MAX)))
+    }
+}
+
+fn parse_identifier(lex: &mut Lexer<Token>, name: String) -> Result<ExprKind, Diagnostic> {
     Ok(if lex.peek() == Some(Ok(Token::LeftParen)) {
         lex.next();
         let mut args = vec![];
@@ -595,20 +1515,20 @@ fn parse_identifier(lex: &mut Lexer<Token>, name: String) -> Result<Expr, String
             args.push(parse_expression(lex)?);
         }
         lex.next();
-        Expr::Call { callee: name, args }
+        ExprKind::Call { callee: name, args }
     } else {
-        Expr::Variable(name)
+        ExprKind::Variable(name)
     })
 }
 
 fn handle_anon_param(args: &mut Vec<String>, expr: &mut Expr) {
-    if let Expr::AnonParam(param) = expr {
-        let name = match &**param {
-            Expr::Call { callee, .. } => callee.clone(),
-            Expr::Variable(name) => name.clone(),
-            Expr::None => {
+    if let ExprKind::AnonParam(param) = &mut expr.kind {
+        let name = match &param.kind {
+            ExprKind::Call { callee, .. } => callee.clone(),
+            ExprKind::Variable(name) => name.clone(),
+            ExprKind::None => {
                 let name = format!("arg{}", args.len());
-                **param = Expr::Variable(name.clone());
+                param.kind = ExprKind::Variable(name.clone());
                 name
             }
             _ => unreachable!(),
@@ -623,14 +1543,14 @@ fn parse_binary_expression(
     lex: &mut Lexer<Token>,
     mut lhs: Expr,
     precedence: u8,
-) -> Result<Expr, String> {
+) -> Result<Expr, Diagnostic> {
     while let Some(Ok(tok)) = lex.peek() {
         let mut args = vec![];
         handle_anon_param(&mut args, &mut lhs);
         let prec = tok.get_precedence();
         if prec < precedence {
-            if let Expr::AnonParam(param) = lhs {
-                lhs = Expr::Closure { args, body: param };
+            if let ExprKind::AnonParam(param) = lhs.kind {
+                lhs = Expr::new(ExprKind::Closure { args, body: param }, lhs.span);
             }
             break;
         }
@@ -645,26 +1565,33 @@ fn parse_binary_expression(
                 } else {
                     0
                 };
-                rhs = parse_binary_expression(lex, rhs, next_prec + is_left_associative)?;
+                rhs = parse_binary_expression(lex, rhs, prec + is_left_associative)?;
             }
         }
         handle_anon_param(&mut args, &mut rhs);
-        lhs = Expr::Binary {
-            op,
-            lhs: Box::new(lhs),
-            rhs: Box::new(rhs),
-        };
+        let span = lhs.span.to(&rhs.span);
+        lhs = Expr::new(
+            ExprKind::Binary {
+                op,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            },
+            span.clone(),
+        );
         if !args.is_empty() {
-            lhs = Expr::Closure {
-                args,
-                body: Box::new(lhs),
-            };
+            lhs = Expr::new(
+                ExprKind::Closure {
+                    args,
+                    body: Box::new(lhs),
+                },
+                span,
+            );
         }
     }
     Ok(lhs)
 }
 
-fn parse_ternary_expression(lex: &mut Lexer<Token>, mut expr: Expr) -> Result<Expr, String> {
+fn parse_ternary_expression(lex: &mut Lexer<Token>, mut expr: Expr) -> Result<Expr, Diagnostic> {
     while lex.peek().is_if() {
         lex.next();
         let condition = parse_expression(lex)?;
@@ -672,20 +1599,68 @@ fn parse_ternary_expression(lex: &mut Lexer<Token>, mut expr: Expr) -> Result<Ex
             return err(lex, "`;` after ternary condition");
         }
         let if_false = parse_expression(lex)?;
-        expr = Expr::Ternary {
-            condition: Box::new(condition),
-            if_true: Box::new(expr),
-            if_false: Box::new(if_false),
-        };
+        let span = expr.span.to(&if_false.span);
+        expr = Expr::new(
+            ExprKind::Ternary {
+                condition: Box::new(condition),
+                if_true: Box::new(expr),
+                if_false: Box::new(if_false),
+            },
+            span,
+        );
     }
     Ok(expr)
 }
 
-fn err<T>(lex: &Lexer<Token>, expect: &str) -> Result<T, String> {
-    Err(format!(
-        "Expected {}, but got `{}` at {:?}.",
-        expect,
-        lex.slice(),
-        lex.span()
-    ))
+/// Diagnostic for a lowercase type name that is too long to be an implicit generic type
+fn unknown_type(lex: &Lexer<Token>, name: &str) -> Diagnostic {
+    let diagnostic = Diagnostic::error(format!("cannot find type `{}` in this scope", name))
+        .with_code("SL0027")
+        .with_primary(lex.token_span(), "not a type");
+    let mut chars = name.chars();
+    let capitalized = chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+        .unwrap_or_default();
+    match similar(&capitalized, BUILTIN_TYPES.map(String::from).iter()) {
+        Some(similar) => diagnostic.with_note(format!("a type named `{}` exists", similar)),
+        None => diagnostic.with_note(format!(
+            "only single letters such as `t` are generic types without a declaration, \
+             so write `'{}` or declare it in `<...>` to make it one",
+            name
+        )),
+    }
+}
+
+fn outside_loop<T>(lex: &Lexer<Token>) -> Result<T, Diagnostic> {
+    Err(
+        Diagnostic::error(format!("`{}` can only be used inside a loop", lex.slice()))
+            .with_code("SL0002")
+            .with_primary(lex.token_span(), "outside of a loop"),
+    )
+}
+
+/// Describes the current token for an error, such as `` `=` `` or `a newline`
+fn found(lex: &Lexer<Token>) -> String {
+    match lex.slice() {
+        "" => String::from("end of file"),
+        "\n" => String::from("a newline"),
+        "\t" => String::from("a tab"),
+        slice => format!("`{}`", slice),
+    }
+}
+
+/// Diagnostic for the current token when `expect` was expected instead,
+/// or for why it could not be lexed
+fn unexpected(lex: &Lexer<Token>, expect: &str) -> Diagnostic {
+    if let Some(Err(error)) = lex.current() {
+        return error.into();
+    }
+    Diagnostic::error(format!("expected {}, found {}", expect, found(lex)))
+        .with_code("SL0001")
+        .with_primary(lex.token_span(), format!("expected {}", expect))
+}
+
+fn err<T>(lex: &Lexer<Token>, expect: &str) -> Result<T, Diagnostic> {
+    Err(unexpected(lex, expect))
 }
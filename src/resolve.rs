@@ -0,0 +1,376 @@
+use std::collections::HashMap;
+
+use crate::{
+    ast::{Expr, ExprKind, Function, Pattern, PatternKind, Program},
+    diagnostic::Diagnostic,
+    span::Span,
+};
+
+/// Checks that every variable and function used in `program` is defined,
+/// and that nothing is defined twice, before any Rust is generated for it
+pub fn resolve(program: &Program) -> Vec<Diagnostic> {
+    let mut resolver = Resolver::default();
+    for import in &program.imports {
+        if import.filename != "std" {
+            resolver.import(&import.filename, &import.span);
+        }
+        for item in &import.items {
+            resolver.import(item, &import.span);
+        }
+    }
+    for r#trait in &program.traits {
+        resolver.declare_global(&r#trait.name, &r#trait.span);
+        resolver.check_unique(r#trait.signatures.iter().map(|s| (&s.name, &s.span)));
+    }
+    for r#enum in &program.enums {
+        resolver.declare_global(&r#enum.name, &r#enum.span);
+    }
+    for r#struct in &program.structs {
+        resolver.declare_global(&r#struct.name, &r#struct.span);
+        resolver.check_unique(r#struct.fields.iter().map(|f| (&f.name, &f.span)));
+        resolver.check_unique(
+            r#struct
+                .methods
+                .iter()
+                .map(|m| (&m.signature.name, &m.signature.span)),
+        );
+    }
+    for function in &program.functions {
+        resolver.declare_global(&function.signature.name, &function.signature.span);
+    }
+    for variable in &program.variables {
+        if let ExprKind::Assign { pattern, .. } = &variable.kind
+            && let PatternKind::Variable(name) = &pattern.kind
+        {
+            resolver.declare_global(name, &pattern.span);
+        }
+    }
+    for variable in &program.variables {
+        if let ExprKind::Assign { value, .. } = &variable.kind {
+            resolver.expr(value);
+        }
+    }
+    let methods = program
+        .structs
+        .iter()
+        .flat_map(|s| &s.methods)
+        .chain(program.impls.iter().flat_map(|i| &i.methods));
+    for function in methods.chain(&program.functions) {
+        resolver.function(function);
+    }
+    resolver.diagnostics
+}
+
+#[derive(Default)]
+struct Resolver {
+    /// Functions, types, global variables and imported names, which are visible everywhere
+    globals: HashMap<String, Span>,
+    /// Parameters and local variables of each block being resolved, innermost last
+    scopes: Vec<Vec<(String, Span)>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Resolver {
+    /// Declares a name that may be imported more than once
+    fn import(&mut self, name: &str, span: &Span) {
+        self.globals
+            .entry(name.to_string())
+            .or_insert_with(|| span.clone());
+    }
+
+    fn declare_global(&mut self, name: &str, span: &Span) {
+        if let Some(first) = self.globals.get(name) {
+            self.diagnostics.push(duplicate(name, span, first));
+        } else {
+            self.globals.insert(name.to_string(), span.clone());
+        }
+    }
+
+    /// Reports the names that appear more than once, such as the fields of a struct
+    fn check_unique<'a>(&mut self, names: impl Iterator<Item = (&'a String, &'a Span)>) {
+        let mut seen: HashMap<&String, &Span> = HashMap::new();
+        for (name, span) in names {
+            if let Some(first) = seen.get(name) {
+                self.diagnostics.push(duplicate(name, span, first));
+            } else {
+                seen.insert(name, span);
+            }
+        }
+    }
+
+    /// Declares a local variable in the innermost scope
+    fn declare(&mut self, name: &str, span: &Span) {
+        let scope = self
+            .scopes
+            .last()
+            .expect("locals are declared inside a scope");
+        let diagnostic = if let Some((_, first)) = scope.iter().find(|(n, _)| n == name) {
+            Some(duplicate(name, span, first))
+        } else {
+            self.lookup(name).map(|outer| {
+                Diagnostic::warning(format!("`{}` shadows an earlier definition", name))
+                    .with_code("SLW0002")
+                    .with_primary(span.clone(), format!("`{}` is defined again here", name))
+                    .with_secondary(outer.clone(), "earlier definition here")
+            })
+        };
+        self.diagnostics.extend(diagnostic);
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .push((name.to_string(), span.clone()));
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Span> {
+        self.scopes
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev())
+            .find(|(n, _)| n == name)
+            .map(|(_, span)| span)
+            .or_else(|| self.globals.get(name))
+    }
+
+    /// The name in scope closest to `name`, if close enough to likely be a typo of it
+    fn similar(&self, name: &str) -> Option<&str> {
+        let names = self.scopes.iter().flatten().map(|(n, _)| n);
+        similar(name, names.chain(self.globals.keys()))
+    }
+
+    /// Reports `name` at `span` if it is not defined, where `kind` is either `value` or `function`
+    fn use_name(&mut self, name: &str, span: Span, kind: &str) {
+        if self.lookup(name).is_some() {
+            return;
+        }
+        let mut diagnostic =
+            Diagnostic::error(format!("cannot find {} `{}` in this scope", kind, name))
+                .with_code(if kind == "value" { "SL0011" } else { "SL0012" })
+                .with_primary(span.clone(), "not found in this scope");
+        if let Some(similar) = self.similar(name) {
+            diagnostic =
+                diagnostic.with_suggestion("a name with a similar spelling exists", span, similar);
+        } else if name == "cout" {
+            diagnostic = diagnostic.with_note("`cout` is imported with `+std:cout`");
+        }
+        self.diagnostics.push(diagnostic);
+    }
+
+    fn function(&mut self, function: &Function) {
+        let signature = &function.signature;
+        self.scopes.push(vec![]);
+        for name in &signature.param_names {
+            self.declare(name, &signature.span);
+        }
+        self.block(&function.body);
+        for equation in &function.equations {
+            self.scopes.push(vec![]);
+            let patterns = equation.parameters_list.iter().zip(&signature.param_names);
+            for (pattern, param) in patterns {
+                // a pattern named after its parameter is that parameter
+                if !matches!(&pattern.kind, PatternKind::Variable(name) if name == param) {
+                    self.pattern(pattern);
+                }
+            }
+            if let Some(guard) = &equation.guard {
+                self.expr(guard);
+            }
+            self.block(&equation.body);
+            self.scopes.pop();
+        }
+        self.scopes.pop();
+    }
+
+    fn block(&mut self, body: &[Expr]) {
+        self.scopes.push(vec![]);
+        for expr in body {
+            self.expr(expr);
+        }
+        self.scopes.pop();
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) {
+        match &pattern.kind {
+            PatternKind::Variable(name) => self.declare(name, &pattern.span),
+            PatternKind::List(patterns)
+            | PatternKind::Tuple(patterns)
+            | PatternKind::Variant(_, _, patterns) => {
+                for pattern in patterns {
+                    self.pattern(pattern);
+                }
+            }
+            PatternKind::StructVariant(_, _, fields) => {
+                for field in fields {
+                    self.declare(field, &pattern.span);
+                }
+            }
+            PatternKind::Literal(_) | PatternKind::Wildcard => (),
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::This
+            | ExprKind::None
+            | ExprKind::Literal(_)
+            | ExprKind::Break(None)
+            | ExprKind::Continue => (),
+            ExprKind::Reference(inner)
+            | ExprKind::Some(inner)
+            | ExprKind::Ok(inner)
+            | ExprKind::Err(inner)
+            | ExprKind::Break(Some(inner)) => self.expr(inner),
+            ExprKind::List(items) | ExprKind::Tuple(items) | ExprKind::Set(items) => {
+                for item in items {
+                    self.expr(item);
+                }
+            }
+            ExprKind::Map(entries) => {
+                for (key, value) in entries {
+                    self.expr(key);
+                    self.expr(value);
+                }
+            }
+            ExprKind::Entry { map, key } => {
+                self.expr(map);
+                self.expr(key);
+            }
+            ExprKind::Variable(name) => self.use_name(name, expr.span.clone(), "value"),
+            // anonymous parameters define themselves as parameters of their closure
+            ExprKind::AnonParam(param) => {
+                if let ExprKind::Call { args, .. } = &param.kind {
+                    for arg in args {
+                        self.expr(arg);
+                    }
+                }
+            }
+            ExprKind::Binary { op, lhs, rhs } if op == "." => {
+                self.expr(lhs);
+                self.member(rhs);
+            }
+            ExprKind::Binary { lhs, rhs, .. } => {
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+            ExprKind::Ternary {
+                condition,
+                if_true,
+                if_false,
+            } => {
+                self.expr(condition);
+                self.expr(if_true);
+                self.expr(if_false);
+            }
+            ExprKind::Call { callee, args } => {
+                let start = expr.span.range.start;
+                let span = Span {
+                    range: start..start + callee.len(),
+                    ..expr.span.clone()
+                };
+                self.use_name(callee, span, "function");
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            ExprKind::Variant { args, .. } => {
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            ExprKind::Assign { pattern, value, .. } => {
+                self.expr(value);
+                self.pattern(pattern);
+            }
+            ExprKind::Closure { body, .. } => self.expr(body),
+            ExprKind::For {
+                pattern,
+                iterable,
+                body,
+            } => {
+                self.expr(iterable);
+                self.scopes.push(vec![]);
+                self.pattern(pattern);
+                self.block(body);
+                self.scopes.pop();
+            }
+            ExprKind::While { condition, body } => {
+                self.expr(condition);
+                self.block(body);
+            }
+            ExprKind::Loop(body) => self.block(body),
+            ExprKind::Match { subject, arms } => {
+                self.expr(subject);
+                for arm in arms {
+                    self.scopes.push(vec![]);
+                    self.pattern(&arm.pattern);
+                    if let Some(guard) = &arm.guard {
+                        self.expr(guard);
+                    }
+                    self.block(&arm.body);
+                    self.scopes.pop();
+                }
+            }
+        }
+    }
+
+    /// Resolves the right side of `.`, whose leading name is a field or method instead of a variable
+    fn member(&mut self, member: &Expr) {
+        match &member.kind {
+            ExprKind::Variable(_) => (),
+            ExprKind::Call { args, .. } => {
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            ExprKind::Entry { map, key } => {
+                self.member(map);
+                self.expr(key);
+            }
+            _ => self.expr(member),
+        }
+    }
+}
+
+fn duplicate(name: &str, span: &Span, first: &Span) -> Diagnostic {
+    let diagnostic = Diagnostic::error(format!("`{}` is defined more than once", name))
+        .with_code("SL0013")
+        .with_primary(span.clone(), format!("`{}` redefined here", name));
+    // parameters share the span of their signature
+    if first == span {
+        diagnostic
+    } else {
+        diagnostic.with_secondary(
+            first.clone(),
+            format!("first definition of `{}` here", name),
+        )
+    }
+}
+
+/// The candidate closest to `name`, if close enough to likely be a typo of it
+pub fn similar<'a>(name: &str, candidates: impl Iterator<Item = &'a String>) -> Option<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    candidates
+        .map(|candidate| (edit_distance(name, candidate), candidate.as_str()))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// Number of characters to insert, remove or replace to turn `a` into `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
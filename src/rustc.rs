@@ -0,0 +1,124 @@
+use serde::Deserialize;
+
+use crate::{diagnostic::Diagnostic, span::SourceMap};
+
+/// A diagnostic from `rustc --error-format=json`, keeping only what is translated
+#[derive(Deserialize)]
+struct RustcDiagnostic {
+    message: String,
+    code: Option<RustcCode>,
+    level: String,
+    spans: Vec<RustcSpan>,
+    children: Vec<RustcDiagnostic>,
+}
+
+#[derive(Deserialize)]
+struct RustcCode {
+    code: String,
+}
+
+#[derive(Deserialize)]
+struct RustcSpan {
+    file_name: String,
+    byte_start: usize,
+    byte_end: usize,
+    line_start: usize,
+    column_start: usize,
+    is_primary: bool,
+    label: Option<String>,
+    suggested_replacement: Option<String>,
+    /// The macro call this span was expanded from, if any
+    expansion: Option<Box<RustcExpansion>>,
+}
+
+#[derive(Deserialize)]
+struct RustcExpansion {
+    span: RustcSpan,
+}
+
+impl RustcSpan {
+    /// Follows macro expansions back to the span written in `rust_file`
+    fn in_file(&self, rust_file: &str) -> Option<&RustcSpan> {
+        if self.file_name == rust_file {
+            Some(self)
+        } else {
+            self.expansion.as_ref()?.span.in_file(rust_file)
+        }
+    }
+}
+
+/// Translates a line of `rustc --error-format=json` output about the generated
+/// `rust_file` into a diagnostic about the Soulite file it was generated from
+///
+/// Returns `None` for summaries such as "aborting due to 2 previous errors",
+/// since those are reported for the Soulite file separately.
+pub fn translate(line: &str, rust_file: &str, source_map: &SourceMap) -> Option<Diagnostic> {
+    let Ok(rustc) = serde_json::from_str::<RustcDiagnostic>(line) else {
+        return (!line.trim().is_empty()).then(|| Diagnostic::error(line));
+    };
+    let mut diagnostic = match rustc.level.as_str() {
+        "error" | "error: internal compiler error" => Diagnostic::error(rustc.message),
+        "warning" => Diagnostic::warning(rustc.message),
+        _ => return None,
+    };
+    if rustc.spans.is_empty()
+        && (diagnostic.message.starts_with("aborting due to")
+            || diagnostic.message.ends_with("emitted"))
+    {
+        return None;
+    }
+    let spans = rustc
+        .spans
+        .iter()
+        .filter_map(|span| Some((span.in_file(rust_file)?, span)));
+    for (span, original) in spans {
+        let label = original.label.clone().unwrap_or_default();
+        match source_map.lookup(span.byte_start..span.byte_end) {
+            Some(sl_span) if original.is_primary && diagnostic.primary.is_none() => {
+                diagnostic = diagnostic.with_primary(sl_span, label);
+            }
+            Some(sl_span)
+                if !label.is_empty()
+                    && diagnostic
+                        .primary
+                        .as_ref()
+                        .is_none_or(|p| p.span != sl_span) =>
+            {
+                diagnostic = diagnostic.with_secondary(sl_span, label);
+            }
+            None if original.is_primary => {
+                diagnostic = diagnostic.with_note(format!(
+                    "in the generated Rust at {}:{}:{}",
+                    rust_file, span.line_start, span.column_start
+                ));
+            }
+            _ => (),
+        }
+    }
+    for child in rustc.children {
+        // fixes suggested by rustc are for the Rust code, not the Soulite code
+        if child
+            .spans
+            .iter()
+            .any(|span| span.suggested_replacement.is_some())
+        {
+            continue;
+        }
+        let sl_span = child.spans.iter().find_map(|span| {
+            let span = span.in_file(rust_file)?;
+            source_map.lookup(span.byte_start..span.byte_end)
+        });
+        diagnostic = match sl_span {
+            Some(sl_span) => diagnostic.with_secondary(sl_span, child.message),
+            None => diagnostic.with_note(child.message),
+        };
+    }
+    if let Some(RustcCode { code }) = rustc.code {
+        if code.starts_with('E') {
+            diagnostic =
+                diagnostic.with_note(format!("`rustc --explain {code}` explains this error"));
+        }
+        diagnostic = diagnostic.with_code(code);
+    }
+    Some(diagnostic)
+}
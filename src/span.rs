@@ -0,0 +1,218 @@
+use std::{fmt, ops::Range, rc::Rc};
+
+use serde::{Serialize, Serializer, ser::SerializeStruct};
+
+/// Location of a node in a Soulite source file
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Span {
+    pub file: Rc<str>,
+    /// Byte range within the file
+    #[serde(flatten, serialize_with = "serialize_range")]
+    pub range: Range<usize>,
+    /// Line of the first byte, starting from 1
+    pub line: usize,
+    /// Column of the first byte in characters, starting from 1
+    pub column: usize,
+}
+
+impl Span {
+    /// Covers everything from the start of `self` to the end of `other`
+    pub fn to(&self, other: &Span) -> Span {
+        Span {
+            range: self.range.start..other.range.end,
+            ..self.clone()
+        }
+    }
+}
+
+/// Serializes `range` as `byte_start` and `byte_end`, as `rustc` does
+fn serialize_range<S: Serializer>(range: &Range<usize>, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut range_struct = serializer.serialize_struct("Range", 2)?;
+    range_struct.serialize_field("byte_start", &range.start)?;
+    range_struct.serialize_field("byte_end", &range.end)?;
+    range_struct.end()
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+/// A source file with the offsets of its lines, used to create [`Span`]s
+#[derive(Debug, PartialEq)]
+pub struct SourceFile {
+    pub name: Rc<str>,
+    pub source: Rc<str>,
+    /// Byte offset where each line starts
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    pub fn new(name: &str, source: &str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        SourceFile {
+            name: Rc::from(name),
+            source: Rc::from(source),
+            line_starts,
+        }
+    }
+
+    pub fn span(&self, range: Range<usize>) -> Span {
+        let line = self
+            .line_starts
+            .partition_point(|&start| start <= range.start);
+        let line_start = self.line_starts[line - 1];
+        Span {
+            file: self.name.clone(),
+            line,
+            column: self.source[line_start..range.start].chars().count() + 1,
+            range,
+        }
+    }
+
+    /// Byte offset where the given line starts, counting lines from 1
+    pub fn line_start(&self, line: usize) -> usize {
+        self.line_starts[line - 1]
+    }
+
+    /// Text of the given line, counting lines from 1, without its newline
+    pub fn line(&self, line: usize) -> &str {
+        let end = self
+            .line_starts
+            .get(line)
+            .map_or(self.source.len(), |next| next - 1);
+        &self.source[self.line_start(line)..end]
+    }
+}
+
+impl Default for SourceFile {
+    fn default() -> Self {
+        SourceFile::new("", "")
+    }
+}
+
+/// Starts a [`mark`]ed region of generated Rust, followed by `<start>..<end>*/`
+const MARK_START: &str = "/*sl:";
+/// Ends a [`mark`]ed region of generated Rust
+const MARK_END: &str = "/*sl*/";
+
+/// Wraps the Rust generated for a node in comments recording its `span`,
+/// which [`SourceMap::new`] strips back out
+pub fn mark(span: &Span, rust: String) -> String {
+    format!(
+        "{MARK_START}{}..{}*/{rust}{MARK_END}",
+        span.range.start, span.range.end
+    )
+}
+
+/// Length of the Rust string or char literal at the start of `rust`,
+/// or 1 if it does not start with one, such as for `/` or a lifetime
+fn literal_len(rust: &str) -> usize {
+    if rust.starts_with('"') {
+        let mut escaped = false;
+        for (i, c) in rust.char_indices().skip(1) {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => return i + 1,
+                _ => (),
+            }
+        }
+        return rust.len();
+    }
+    let mut chars = rust.chars().skip(1);
+    match (rust.starts_with('\''), chars.next(), chars.next()) {
+        (true, Some('\\'), _) => rust
+            .get(3..)
+            .and_then(|rest| rest.find('\''))
+            .map_or(1, |i| i + 4),
+        (true, Some(c), Some('\'')) => c.len_utf8() + 2,
+        _ => 1,
+    }
+}
+
+/// Maps byte ranges of generated Rust back to the Soulite source they were generated from
+pub struct SourceMap {
+    file: Rc<SourceFile>,
+    /// Range of each [`mark`]ed region of the Rust, paired with the range of its node in `file`
+    regions: Vec<(Range<usize>, Range<usize>)>,
+}
+
+impl SourceMap {
+    /// Strips the [`mark`]s from the generated Rust, returning it along with where its regions came from
+    pub fn new(file: Rc<SourceFile>, marked: &str) -> (String, SourceMap) {
+        let mut rust = String::with_capacity(marked.len());
+        let mut regions = vec![];
+        let mut open = vec![];
+        let mut rest = marked;
+        while let Some(i) = rest.find(['/', '"', '\'']) {
+            rust.push_str(&rest[..i]);
+            rest = &rest[i..];
+            if let Some(after) = rest.strip_prefix(MARK_END) {
+                if let Some((start, source)) = open.pop() {
+                    regions.push((start..rust.len(), source));
+                }
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix(MARK_START)
+                && let Some((range, after)) = after.split_once("*/")
+                && let Some((start, end)) = range.split_once("..")
+                && let (Ok(start), Ok(end)) = (start.parse(), end.parse())
+            {
+                open.push((rust.len(), start..end));
+                rest = after;
+            } else {
+                // string and char literals are kept whole, so a `/*sl` written in one stays
+                let len = literal_len(rest);
+                rust.push_str(&rest[..len]);
+                rest = &rest[len..];
+            }
+        }
+        rust.push_str(rest);
+        (rust, SourceMap { file, regions })
+    }
+
+    /// Span of the innermost node whose generated Rust contains `range`
+    pub fn lookup(&self, range: Range<usize>) -> Option<Span> {
+        self.regions
+            .iter()
+            .filter(|(rust, _)| rust.start <= range.start && range.end <= rust.end)
+            .min_by_key(|(rust, _)| rust.len())
+            .map(|(_, source)| self.file.span(source.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip(marked: &str) -> String {
+        let file = Rc::new(SourceFile::new("test.sl", "cout <| x"));
+        SourceMap::new(file, marked).0
+    }
+
+    #[test]
+    fn strips_marks() {
+        let marked = format!("let x = {};", mark(&Span::default(), String::from("1 + 2")));
+        assert_eq!(strip(&marked), "let x = 1 + 2;");
+    }
+
+    #[test]
+    fn keeps_marks_in_literals() {
+        let marked = mark(&Span::default(), String::from(r#"format!("a/*sl*/b")"#));
+        assert_eq!(strip(&marked), r#"format!("a/*sl*/b")"#);
+        let marked = r#"("\"/*sl:0..1*/", '"', '\'', x/*sl*/)"#;
+        assert_eq!(strip(marked), r#"("\"/*sl:0..1*/", '"', '\'', x)"#);
+    }
+
+    #[test]
+    fn maps_regions_to_source() {
+        let file = Rc::new(SourceFile::new("test.sl", "cout <| x"));
+        let marked = format!("print!({})", mark(&file.span(8..9), String::from("x")));
+        let (rust, source_map) = SourceMap::new(file, &marked);
+        assert_eq!(rust, "print!(x)");
+        assert_eq!(source_map.lookup(7..8).map(|span| span.range), Some(8..9));
+    }
+}
@@ -0,0 +1,296 @@
+use std::{collections::HashMap, mem::discriminant};
+
+use crate::{
+    ast::{Bound, Impl, Program, Trait, Type, TypeKind, TypeSignature},
+    diagnostic::Diagnostic,
+    resolve::similar,
+    span::Span,
+    typeck::Ty,
+};
+
+/// Traits from `std` that generic types can be bounded by without declaring them
+const STD_TRAITS: [&str; 9] = [
+    "Clone",
+    "Copy",
+    "Debug",
+    "Default",
+    "Display",
+    "Eq",
+    "Ord",
+    "PartialEq",
+    "PartialOrd",
+];
+
+/// Checks that every `<struct> => <trait>` impl has exactly the methods its trait
+/// declares, with the same parameter and return types, and that every bound on a
+/// generic type names a trait
+///
+/// The types that the generic types of each trait stand for are stored in [`Impl::trait_types`].
+pub fn check(program: &mut Program) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    for r#impl in &mut program.impls {
+        let is_type = program.structs.iter().any(|s| s.name == r#impl.struct_name)
+            || program.enums.iter().any(|e| e.name == r#impl.struct_name);
+        if !is_type {
+            let types = program
+                .structs
+                .iter()
+                .map(|s| &s.name)
+                .chain(program.enums.iter().map(|e| &e.name));
+            diagnostics.push(not_found(
+                "struct",
+                &r#impl.struct_name,
+                &r#impl.span,
+                types,
+            ));
+        }
+        match program.traits.iter().find(|t| t.name == r#impl.trait_name) {
+            Some(r#trait) => conform(r#impl, r#trait, &mut diagnostics),
+            None => diagnostics.push(not_found(
+                "trait",
+                &r#impl.trait_name,
+                &r#impl.span,
+                program.traits.iter().map(|t| &t.name),
+            )),
+        }
+    }
+    let traits = program
+        .traits
+        .iter()
+        .map(|t| t.name.clone())
+        .chain(STD_TRAITS.map(String::from))
+        .collect::<Vec<_>>();
+    // impls share the bounds they inherit from their struct, so each is reported once
+    let mut reported = vec![];
+    for r#trait in bounds(program).flat_map(|bound| &bound.traits) {
+        if let TypeKind::Named(name, _) | TypeKind::Generic(name) = &r#trait.kind
+            && !traits.contains(name)
+            && !reported.contains(&&r#trait.span)
+        {
+            reported.push(&r#trait.span);
+            diagnostics.push(not_found("trait", name, &r#trait.span, traits.iter()));
+        }
+    }
+    diagnostics
+}
+
+/// The bounds on the generic types of every item and function in `program`
+fn bounds(program: &Program) -> impl Iterator<Item = &Bound> {
+    let signatures = program
+        .functions
+        .iter()
+        .chain(program.structs.iter().flat_map(|s| &s.methods))
+        .chain(program.impls.iter().flat_map(|i| &i.methods))
+        .map(|f| &f.signature)
+        .chain(program.traits.iter().flat_map(|t| &t.signatures));
+    program
+        .structs
+        .iter()
+        .flat_map(|s| &s.bounds)
+        .chain(program.enums.iter().flat_map(|e| &e.bounds))
+        .chain(program.traits.iter().flat_map(|t| &t.bounds))
+        .chain(program.impls.iter().flat_map(|i| &i.bounds))
+        .chain(signatures.flat_map(|s| &s.bounds))
+}
+
+fn not_found<'a>(
+    kind: &str,
+    name: &str,
+    span: &Span,
+    candidates: impl Iterator<Item = &'a String>,
+) -> Diagnostic {
+    let diagnostic = Diagnostic::error(format!("cannot find {} `{}` in this scope", kind, name))
+        .with_code("SL0020")
+        .with_primary(span.clone(), format!("no {} named `{}`", kind, name));
+    match similar(name, candidates) {
+        Some(similar) => diagnostic.with_note(format!("a {} named `{}` exists", kind, similar)),
+        None => diagnostic,
+    }
+}
+
+fn conform(r#impl: &mut Impl, r#trait: &Trait, diagnostics: &mut Vec<Diagnostic>) {
+    let errors = diagnostics.len();
+    let missing = r#trait.signatures.iter().filter(|signature| {
+        !r#impl
+            .methods
+            .iter()
+            .any(|m| m.signature.name == signature.name)
+    });
+    for signature in missing {
+        diagnostics.push(
+            Diagnostic::error(format!(
+                "`{}` is missing the method `{}` of trait `{}`",
+                r#impl.struct_name, signature.name, r#trait.name
+            ))
+            .with_code("SL0021")
+            .with_primary(r#impl.span.clone(), format!("missing `{}`", signature.name))
+            .with_secondary(
+                signature.span.clone(),
+                format!("`{}` is declared in the trait here", signature.name),
+            ),
+        );
+    }
+    // generic types of the trait stand for the same type everywhere in the impl
+    let mut generics = HashMap::new();
+    for method in &r#impl.methods {
+        let method = &method.signature;
+        match r#trait.signatures.iter().find(|s| s.name == method.name) {
+            Some(declared) => compare(method, declared, r#trait, &mut generics, diagnostics),
+            None => diagnostics.push(
+                Diagnostic::error(format!(
+                    "method `{}` is not a member of trait `{}`",
+                    method.name, r#trait.name
+                ))
+                .with_code("SL0022")
+                .with_primary(method.span.clone(), "not a member of the trait")
+                .with_secondary(
+                    r#trait.span.clone(),
+                    format!("trait `{}` is declared here", r#trait.name),
+                )
+                .with_note(
+                    "methods that are not part of the trait belong in the struct declaration",
+                ),
+            ),
+        }
+    }
+    if diagnostics.len() > errors {
+        return;
+    }
+    for generic in &r#trait.generics {
+        match generics.remove(generic) {
+            Some(ty) => r#impl.trait_types.push(ty),
+            None => diagnostics.push(
+                Diagnostic::error(format!(
+                    "cannot infer the type of `{}` in trait `{}`",
+                    generic, r#trait.name
+                ))
+                .with_code("SL0016")
+                .with_primary(r#impl.span.clone(), format!("cannot infer `{}`", generic))
+                .with_secondary(
+                    r#trait.span.clone(),
+                    format!("trait `{}` is declared here", r#trait.name),
+                )
+                .with_note(format!(
+                    "the impl decides `{}` through the methods of the trait that use it",
+                    generic
+                )),
+            ),
+        }
+    }
+}
+
+/// Reports the differences between the signature of a `method` and its `declared` signature in the trait
+fn compare(
+    method: &TypeSignature,
+    declared: &TypeSignature,
+    r#trait: &Trait,
+    generics: &mut HashMap<String, Ty>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    if method.param_types.len() != declared.param_types.len() {
+        let count = declared.param_types.len();
+        diagnostics.push(
+            Diagnostic::error(format!(
+                "method `{}` has {} parameter{} but its declaration in trait `{}` has {}",
+                method.name,
+                method.param_types.len(),
+                plural(method.param_types.len()),
+                r#trait.name,
+                count
+            ))
+            .with_code("SL0023")
+            .with_primary(
+                method.span.clone(),
+                format!("expected {} parameter{}", count, plural(count)),
+            )
+            .with_secondary(declared.span.clone(), "trait declaration here"),
+        );
+        return;
+    }
+    let returns = if method.return_types.len() == declared.return_types.len() {
+        method
+            .return_types
+            .iter()
+            .zip(&declared.return_types)
+            .collect()
+    } else {
+        diagnostics.push(
+            incompatible(method, r#trait)
+                .with_primary(
+                    method.span.clone(),
+                    format!(
+                        "expected `{}`, found `{}`",
+                        Ty::from_returns(&declared.return_types),
+                        Ty::from_returns(&method.return_types)
+                    ),
+                )
+                .with_secondary(declared.span.clone(), "trait declaration here"),
+        );
+        vec![]
+    };
+    let types = method
+        .param_types
+        .iter()
+        .zip(&declared.param_types)
+        .chain(returns);
+    for (found, expected) in types {
+        if !fits(
+            &Ty::from_type(expected),
+            &Ty::from_type(found),
+            &r#trait.generics,
+            generics,
+        ) {
+            diagnostics.push(mismatch(method, r#trait, found, expected));
+        }
+    }
+}
+
+fn plural(n: usize) -> &'static str {
+    if n == 1 { "" } else { "s" }
+}
+
+fn incompatible(method: &TypeSignature, r#trait: &Trait) -> Diagnostic {
+    Diagnostic::error(format!(
+        "method `{}` has an incompatible type for trait `{}`",
+        method.name, r#trait.name
+    ))
+    .with_code("SL0023")
+}
+
+fn mismatch(method: &TypeSignature, r#trait: &Trait, found: &Type, expected: &Type) -> Diagnostic {
+    incompatible(method, r#trait)
+        .with_primary(
+            found.span.clone(),
+            format!(
+                "expected `{}`, found `{}`",
+                Ty::from_type(expected),
+                Ty::from_type(found)
+            ),
+        )
+        .with_secondary(expected.span.clone(), "type in the trait declaration")
+}
+
+/// Whether `found` is `expected`, where the `generics` of the trait
+/// may be any type as long as it is the same one in every place
+fn fits(expected: &Ty, found: &Ty, generics: &[String], bound: &mut HashMap<String, Ty>) -> bool {
+    if let Ty::Generic(name) = expected
+        && generics.contains(name)
+    {
+        return bound.entry(name.clone()).or_insert_with(|| found.clone()) == found;
+    }
+    let same_kind = match (expected, found) {
+        (Ty::Unsigned(a), Ty::Unsigned(b))
+        | (Ty::Integer(a), Ty::Integer(b))
+        | (Ty::Float(a), Ty::Float(b)) => a == b,
+        (Ty::Array(_, a), Ty::Array(_, b)) => a == b,
+        (Ty::Generic(a), Ty::Generic(b)) | (Ty::Named(a, _), Ty::Named(b, _)) => a == b,
+        _ => discriminant(expected) == discriminant(found),
+    };
+    let (expected, found) = (expected.children(), found.children());
+    same_kind
+        && expected.len() == found.len()
+        && expected
+            .into_iter()
+            .zip(found)
+            .all(|(e, f)| fits(e, f, generics, bound))
+}
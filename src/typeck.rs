@@ -0,0 +1,1495 @@
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    fmt,
+};
+
+use crate::{
+    ast::{
+        AssignType, Expr, ExprKind, Field, Function, Literal, Pattern, PatternKind, Program,
+        ToRust, Type, TypeKind, TypeSignature, Variant, is_assignment,
+    },
+    diagnostic::Diagnostic,
+    span::Span,
+};
+
+/// Type of an expression, as inferred by [`check`]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Ty {
+    Unsigned(u8),
+    Integer(u8),
+    Float(u8),
+    Bool,
+    String,
+    Reference(Box<Ty>),
+    List(Box<Ty>),
+    Array(Box<Ty>, usize),
+    Option(Box<Ty>),
+    Result(Box<Ty>, Box<Ty>),
+    Generic(String),
+    /// A struct or enum with its generic type arguments
+    Named(String, Vec<Ty>),
+    /// The empty tuple is the unit type
+    Tuple(Vec<Ty>),
+    Map(Box<Ty>, Box<Ty>),
+    Set(Box<Ty>),
+    /// `<start>..<end>`
+    Range(Box<Ty>),
+    /// Parameter types and return type of a function or closure
+    Function(Vec<Ty>, Box<Ty>),
+    /// A type not inferred yet
+    Var(usize),
+}
+
+impl Ty {
+    fn unit() -> Ty {
+        Ty::Tuple(vec![])
+    }
+
+    pub fn from_type(t: &Type) -> Ty {
+        match &t.kind {
+            TypeKind::Unsigned(bits) => Ty::Unsigned(*bits),
+            TypeKind::Integer(bits) => Ty::Integer(*bits),
+            TypeKind::Float(bits) => Ty::Float(*bits),
+            TypeKind::String => Ty::String,
+            TypeKind::Reference(inner) => Ty::Reference(Box::new(Ty::from_type(inner))),
+            TypeKind::List(inner) => Ty::List(Box::new(Ty::from_type(inner))),
+            TypeKind::Array(inner, size) => Ty::Array(Box::new(Ty::from_type(inner)), *size),
+            TypeKind::Option(inner) => Ty::Option(Box::new(Ty::from_type(inner))),
+            TypeKind::Result(inner, err) => {
+                Ty::Result(Box::new(Ty::from_type(inner)), Box::new(Ty::from_type(err)))
+            }
+            TypeKind::Generic(name) => Ty::Generic(name.clone()),
+            TypeKind::Named(name, args) => {
+                Ty::Named(name.clone(), args.iter().map(Ty::from_type).collect())
+            }
+            TypeKind::Tuple(types) => Ty::Tuple(types.iter().map(Ty::from_type).collect()),
+            TypeKind::Map(key, value) => {
+                Ty::Map(Box::new(Ty::from_type(key)), Box::new(Ty::from_type(value)))
+            }
+            TypeKind::Set(inner) => Ty::Set(Box::new(Ty::from_type(inner))),
+            TypeKind::Closure(arg_types, return_types) => Ty::Function(
+                arg_types.iter().map(Ty::from_type).collect(),
+                Box::new(Ty::from_returns(return_types)),
+            ),
+        }
+    }
+
+    /// A single return type as is, several as a tuple and none as the unit type
+    pub fn from_returns(types: &[Type]) -> Ty {
+        match types {
+            [single] => Ty::from_type(single),
+            _ => Ty::Tuple(types.iter().map(Ty::from_type).collect()),
+        }
+    }
+
+    pub fn children(&self) -> Vec<&Ty> {
+        match self {
+            Ty::Unsigned(_)
+            | Ty::Integer(_)
+            | Ty::Float(_)
+            | Ty::Bool
+            | Ty::String
+            | Ty::Generic(_)
+            | Ty::Var(_) => vec![],
+            Ty::Reference(inner)
+            | Ty::List(inner)
+            | Ty::Array(inner, _)
+            | Ty::Option(inner)
+            | Ty::Set(inner)
+            | Ty::Range(inner) => vec![inner],
+            Ty::Result(a, b) | Ty::Map(a, b) => vec![a, b],
+            Ty::Named(_, types) | Ty::Tuple(types) => types.iter().collect(),
+            Ty::Function(params, ret) => params.iter().chain([&**ret]).collect(),
+        }
+    }
+
+    /// Whether `f` holds for this type or any type inside it
+    fn contains(&self, f: &impl Fn(&Ty) -> bool) -> bool {
+        f(self) || self.children().into_iter().any(|child| child.contains(f))
+    }
+
+    /// Names of the generic types used in this type, in order of appearance
+    fn generics(&self, names: &mut Vec<String>) {
+        match self {
+            Ty::Generic(name) if !names.contains(name) => names.push(name.clone()),
+            _ => self
+                .children()
+                .into_iter()
+                .for_each(|child| child.generics(names)),
+        }
+    }
+
+    /// Rebuilds this type, replacing each part for which `f` returns a type
+    fn map<F: FnMut(&Ty) -> Option<Ty>>(&self, f: &mut F) -> Ty {
+        if let Some(ty) = f(self) {
+            return ty;
+        }
+        let mut map_all = |types: &[Ty]| types.iter().map(|ty| ty.map(f)).collect();
+        match self {
+            Ty::Reference(inner) => Ty::Reference(Box::new(inner.map(f))),
+            Ty::List(inner) => Ty::List(Box::new(inner.map(f))),
+            Ty::Array(inner, size) => Ty::Array(Box::new(inner.map(f)), *size),
+            Ty::Option(inner) => Ty::Option(Box::new(inner.map(f))),
+            Ty::Set(inner) => Ty::Set(Box::new(inner.map(f))),
+            Ty::Range(inner) => Ty::Range(Box::new(inner.map(f))),
+            Ty::Result(a, b) => Ty::Result(Box::new(a.map(f)), Box::new(b.map(f))),
+            Ty::Map(a, b) => Ty::Map(Box::new(a.map(f)), Box::new(b.map(f))),
+            Ty::Named(name, types) => Ty::Named(name.clone(), map_all(types)),
+            Ty::Tuple(types) => Ty::Tuple(map_all(types)),
+            Ty::Function(params, ret) => {
+                let params = map_all(params);
+                Ty::Function(params, Box::new(ret.map(f)))
+            }
+            _ => self.clone(),
+        }
+    }
+
+    /// Replaces the generic types named in `types` with their types
+    pub fn substitute(&self, types: &HashMap<String, Ty>) -> Ty {
+        self.map(&mut |ty| match ty {
+            Ty::Generic(name) => types.get(name).cloned(),
+            _ => None,
+        })
+    }
+
+    /// The smallest and largest values of an integer type, limited to what a literal can hold
+    pub fn integer_range(&self) -> Option<(i128, u128)> {
+        match self {
+            Ty::Unsigned(128) => Some((0, u128::MAX)),
+            Ty::Unsigned(bits) => Some((0, (1 << bits) - 1)),
+            Ty::Integer(bits) => {
+                let min = -1 << (bits - 1);
+                Some((min, !min as u128))
+            }
+            _ => None,
+        }
+    }
+
+    /// Rust type of this type, with `_` for what was not inferred
+    pub fn to_rust(&self) -> String {
+        let join = |types: &[Ty]| types.iter().map(Ty::to_rust).collect::<Vec<_>>().join(",");
+        match self {
+            Ty::Unsigned(bits) => format!("u{bits}"),
+            Ty::Integer(bits) => format!("i{bits}"),
+            Ty::Float(bits) => format!("f{bits}"),
+            Ty::Bool => String::from("bool"),
+            Ty::String => String::from("String"),
+            Ty::Reference(inner) => format!("&{}", inner.to_rust()),
+            Ty::List(inner) => format!("Vec<{}>", inner.to_rust()),
+            Ty::Array(inner, size) => format!("[{};{}]", inner.to_rust(), size),
+            Ty::Option(inner) => format!("Option<{}>", inner.to_rust()),
+            Ty::Result(inner, err) => format!("Result<{},{}>", inner.to_rust(), err.to_rust()),
+            Ty::Generic(name) => name.to_rust(),
+            Ty::Named(name, args) if args.is_empty() => name.to_rust(),
+            Ty::Named(name, args) => format!("{}<{}>", name.to_rust(), join(args)),
+            Ty::Tuple(types) if types.len() == 1 => format!("({},)", types[0].to_rust()),
+            Ty::Tuple(types) => format!("({})", join(types)),
+            Ty::Map(key, value) => format!(
+                "std::collections::HashMap<{},{}>",
+                key.to_rust(),
+                value.to_rust()
+            ),
+            Ty::Set(inner) => format!("std::collections::HashSet<{}>", inner.to_rust()),
+            Ty::Range(inner) => format!("std::ops::Range<{}>", inner.to_rust()),
+            // closures have no type that can be written in a `let`
+            Ty::Function(..) | Ty::Var(_) => String::from("_"),
+        }
+    }
+}
+
+/// Writes the type in Soulite syntax, such as `{String => Z64}`
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let join = |types: &[Ty]| {
+            types
+                .iter()
+                .map(Ty::to_string)
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        match self {
+            Ty::Unsigned(bits) => write!(f, "N{bits}"),
+            Ty::Integer(bits) => write!(f, "Z{bits}"),
+            Ty::Float(bits) => write!(f, "R{bits}"),
+            Ty::Bool => write!(f, "Bool"),
+            Ty::String => write!(f, "String"),
+            Ty::Reference(inner) => write!(f, "*{inner}"),
+            Ty::List(inner) => write!(f, "[{inner}]"),
+            Ty::Array(inner, size) => write!(f, "{inner}[{size}]"),
+            Ty::Option(inner) => write!(f, "{inner}?"),
+            Ty::Result(inner, err) => write!(f, "{inner}!{err}"),
+            Ty::Generic(name) => write!(f, "{name}"),
+            Ty::Named(name, args) if args.is_empty() => write!(f, "{name}"),
+            Ty::Named(name, args) => {
+                let args = args.iter().map(Ty::to_string).collect::<Vec<_>>();
+                write!(f, "{name}<{}>", args.join(","))
+            }
+            Ty::Tuple(types) => write!(f, "({})", join(types)),
+            Ty::Map(key, value) => write!(f, "{{{key} => {value}}}"),
+            Ty::Set(inner) => write!(f, "{{{inner}}}"),
+            Ty::Range(inner) => write!(f, "{inner}..{inner}"),
+            Ty::Function(params, ret) if params.is_empty() => write!(f, "(-> {ret})"),
+            Ty::Function(params, ret) => write!(f, "({} -> {ret})", join(params)),
+            Ty::Var(_) => write!(f, "_"),
+        }
+    }
+}
+
+/// What an inference variable may still become
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Any,
+    /// Any integer type, from an integer literal
+    Integer,
+    /// Any float type, from a float literal
+    Float,
+}
+
+enum Var {
+    Bound(Ty),
+    Unbound {
+        kind: Kind,
+        /// Whether it stands for something the checker knows nothing about,
+        /// such as the result of a Rust method, so rustc infers it instead
+        opaque: bool,
+    },
+}
+
+/// Parameter and return types of a function, whose generic types are
+/// replaced with fresh inference variables at each call
+#[derive(Clone)]
+struct Signature {
+    params: Vec<Ty>,
+    ret: Ty,
+}
+
+impl Signature {
+    fn new(signature: &TypeSignature) -> Self {
+        Signature {
+            params: signature.param_types.iter().map(Ty::from_type).collect(),
+            ret: Ty::from_returns(&signature.return_types),
+        }
+    }
+}
+
+#[derive(Default)]
+struct StructInfo {
+    generics: Vec<String>,
+    fields: Vec<(String, Ty)>,
+    methods: HashMap<String, Signature>,
+}
+
+enum VariantInfo {
+    Unit,
+    Tuple(Vec<Ty>),
+    Struct(Vec<(String, Ty)>),
+}
+
+struct EnumInfo {
+    generics: Vec<String>,
+    variants: HashMap<String, VariantInfo>,
+}
+
+/// Infers the type of every expression in `program`, storing it in [`Expr::ty`]
+/// for codegen, and reports the expressions whose types do not fit together
+pub fn check(program: &mut Program) -> Vec<Diagnostic> {
+    let mut checker = Checker::new(program);
+    for variable in &mut program.variables {
+        checker.expr(variable);
+    }
+    for r#struct in &mut program.structs {
+        let this = this_type(&r#struct.name, &r#struct.generics);
+        for method in &mut r#struct.methods {
+            checker.function(method, Some(this.clone()));
+        }
+    }
+    for r#impl in &mut program.impls {
+        let this = this_type(&r#impl.struct_name, &r#impl.generic_types);
+        for method in &mut r#impl.methods {
+            checker.function(method, Some(this.clone()));
+        }
+    }
+    for function in &mut program.functions {
+        checker.function(function, None);
+    }
+    let methods = program
+        .structs
+        .iter_mut()
+        .flat_map(|s| &mut s.methods)
+        .chain(program.impls.iter_mut().flat_map(|i| &mut i.methods));
+    for function in methods.chain(&mut program.functions) {
+        for expr in &mut function.body {
+            checker.finish(expr);
+        }
+        for equation in &mut function.equations {
+            for expr in equation.guard.iter_mut().chain(&mut equation.body) {
+                checker.finish(expr);
+            }
+        }
+    }
+    for variable in &mut program.variables {
+        checker.finish(variable);
+        if let ExprKind::Assign {
+            pattern,
+            assign_type: AssignType::Const | AssignType::Static,
+            value,
+            type_hint: None,
+            ..
+        } = &variable.kind
+            && value
+                .ty
+                .as_ref()
+                .is_some_and(|ty| ty.contains(&|t| matches!(t, Ty::Var(_))))
+        {
+            checker.diagnostics.push(
+                Diagnostic::error("type annotations needed")
+                    .with_code("SL0016")
+                    .with_primary(
+                        pattern.span.clone(),
+                        match &pattern.kind {
+                            PatternKind::Variable(name) => {
+                                format!("cannot infer the full type of `{}`", name)
+                            }
+                            _ => String::from("cannot infer the full type of this pattern"),
+                        },
+                    )
+                    .with_note(
+                        "constants and statics need a concrete type, such as `NAME: Z64 = 0`",
+                    ),
+            );
+        }
+    }
+    checker.diagnostics
+}
+
+/// Type of `.` inside the methods of `name`
+fn this_type(name: &str, generics: &[String]) -> Ty {
+    Ty::Named(
+        name.to_string(),
+        generics.iter().cloned().map(Ty::Generic).collect(),
+    )
+}
+
+struct Checker {
+    functions: HashMap<String, Signature>,
+    structs: HashMap<String, StructInfo>,
+    enums: HashMap<String, EnumInfo>,
+    /// Types of global variables
+    globals: HashMap<String, Ty>,
+    /// Types of the parameters and local variables of each block being checked, innermost last
+    scopes: Vec<Vec<(String, Ty)>>,
+    /// Type of the value each enclosing loop breaks with, which only `@` loops have
+    loops: Vec<Option<Ty>>,
+    /// Type of `.` in the method being checked
+    this: Option<Ty>,
+    vars: Vec<Var>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker {
+    fn new(program: &Program) -> Self {
+        let functions = program
+            .functions
+            .iter()
+            .map(|f| (f.signature.name.clone(), Signature::new(&f.signature)))
+            .collect();
+        let mut structs: HashMap<String, StructInfo> = HashMap::new();
+        for r#struct in &program.structs {
+            let info = structs.entry(r#struct.name.clone()).or_default();
+            info.generics = r#struct.generics.clone();
+            info.fields = fields(&r#struct.fields);
+            info.methods.extend(
+                r#struct
+                    .methods
+                    .iter()
+                    .map(|m| (m.signature.name.clone(), Signature::new(&m.signature))),
+            );
+        }
+        for r#impl in &program.impls {
+            let info = structs.entry(r#impl.struct_name.clone()).or_default();
+            info.methods.extend(
+                r#impl
+                    .methods
+                    .iter()
+                    .map(|m| (m.signature.name.clone(), Signature::new(&m.signature))),
+            );
+        }
+        let enums = program
+            .enums
+            .iter()
+            .map(|e| {
+                let variants = e.variants.iter().map(|variant| match variant {
+                    Variant::Unit(name) => (name.clone(), VariantInfo::Unit),
+                    Variant::Tuple(name, types) => (
+                        name.clone(),
+                        VariantInfo::Tuple(types.iter().map(Ty::from_type).collect()),
+                    ),
+                    Variant::Struct(name, fields_) => {
+                        (name.clone(), VariantInfo::Struct(fields(fields_)))
+                    }
+                });
+                let info = EnumInfo {
+                    generics: e.generics.clone(),
+                    variants: variants.collect(),
+                };
+                (e.name.clone(), info)
+            })
+            .collect();
+        Checker {
+            functions,
+            structs,
+            enums,
+            globals: HashMap::new(),
+            scopes: vec![],
+            loops: vec![],
+            this: None,
+            vars: vec![],
+            diagnostics: vec![],
+        }
+    }
+
+    fn fresh(&mut self, kind: Kind) -> Ty {
+        self.vars.push(Var::Unbound {
+            kind,
+            opaque: false,
+        });
+        Ty::Var(self.vars.len() - 1)
+    }
+
+    /// A type the checker cannot know, left for rustc to infer
+    fn unknown(&mut self) -> Ty {
+        self.vars.push(Var::Unbound {
+            kind: Kind::Any,
+            opaque: true,
+        });
+        Ty::Var(self.vars.len() - 1)
+    }
+
+    /// Follows bound inference variables until reaching a type that is not one
+    fn resolve(&self, ty: &Ty) -> Ty {
+        match ty {
+            Ty::Var(i) if let Var::Bound(bound) = &self.vars[*i] => self.resolve(bound),
+            _ => ty.clone(),
+        }
+    }
+
+    /// Resolves every inference variable inside `ty` as well
+    fn resolve_all(&self, ty: &Ty) -> Ty {
+        ty.map(&mut |t| match t {
+            Ty::Var(i) if let Var::Bound(bound) = &self.vars[*i] => Some(self.resolve_all(bound)),
+            _ => None,
+        })
+    }
+
+    /// `ty` for messages, showing integers and floats not inferred yet like rustc does
+    fn describe(&self, ty: &Ty) -> String {
+        self.resolve_all(ty)
+            .map(&mut |t| match t {
+                Ty::Var(i) => match self.vars[*i] {
+                    Var::Unbound {
+                        kind: Kind::Integer,
+                        ..
+                    } => Some(Ty::Generic(String::from("{integer}"))),
+                    Var::Unbound {
+                        kind: Kind::Float, ..
+                    } => Some(Ty::Generic(String::from("{float}"))),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .to_string()
+    }
+
+    fn unify(&mut self, a: &Ty, b: &Ty) -> bool {
+        match (self.resolve(a), self.resolve(b)) {
+            (Ty::Var(x), Ty::Var(y)) if x == y => true,
+            (Ty::Var(x), Ty::Var(y)) => {
+                let (
+                    Var::Unbound {
+                        kind: x_kind,
+                        opaque: x_opaque,
+                    },
+                    Var::Unbound {
+                        kind: y_kind,
+                        opaque: y_opaque,
+                    },
+                ) = (&self.vars[x], &self.vars[y])
+                else {
+                    unreachable!("resolved variables are unbound");
+                };
+                let kind = match (*x_kind, *y_kind) {
+                    (Kind::Any, kind) | (kind, Kind::Any) => kind,
+                    (x_kind, y_kind) if x_kind == y_kind => x_kind,
+                    _ => return false,
+                };
+                self.vars[y] = Var::Unbound {
+                    kind,
+                    opaque: *x_opaque || *y_opaque,
+                };
+                self.vars[x] = Var::Bound(Ty::Var(y));
+                true
+            }
+            (Ty::Var(x), ty) | (ty, Ty::Var(x)) => self.bind(x, ty),
+            (Ty::Unsigned(x), Ty::Unsigned(y))
+            | (Ty::Integer(x), Ty::Integer(y))
+            | (Ty::Float(x), Ty::Float(y)) => x == y,
+            (Ty::Bool, Ty::Bool) | (Ty::String, Ty::String) => true,
+            (Ty::Reference(x), Ty::Reference(y))
+            | (Ty::List(x), Ty::List(y))
+            | (Ty::Option(x), Ty::Option(y))
+            | (Ty::Set(x), Ty::Set(y))
+            | (Ty::Range(x), Ty::Range(y)) => self.unify(&x, &y),
+            (Ty::Array(x, n), Ty::Array(y, m)) => n == m && self.unify(&x, &y),
+            (Ty::Result(x1, x2), Ty::Result(y1, y2)) | (Ty::Map(x1, x2), Ty::Map(y1, y2)) => {
+                self.unify(&x1, &y1) && self.unify(&x2, &y2)
+            }
+            (Ty::Generic(x), Ty::Generic(y)) => x == y,
+            (Ty::Named(x, xs), Ty::Named(y, ys)) => x == y && self.unify_all(&xs, &ys),
+            (Ty::Tuple(xs), Ty::Tuple(ys)) => self.unify_all(&xs, &ys),
+            (Ty::Function(xs, x), Ty::Function(ys, y)) => {
+                self.unify_all(&xs, &ys) && self.unify(&x, &y)
+            }
+            _ => false,
+        }
+    }
+
+    fn unify_all(&mut self, xs: &[Ty], ys: &[Ty]) -> bool {
+        xs.len() == ys.len() && xs.iter().zip(ys).all(|(x, y)| self.unify(x, y))
+    }
+
+    /// Binds the unbound variable `var` to `ty`, which is not a variable itself
+    fn bind(&mut self, var: usize, ty: Ty) -> bool {
+        let Var::Unbound { kind, .. } = self.vars[var] else {
+            unreachable!("resolved variables are unbound");
+        };
+        let fits = match kind {
+            Kind::Any => true,
+            Kind::Integer => matches!(ty, Ty::Unsigned(_) | Ty::Integer(_)),
+            Kind::Float => matches!(ty, Ty::Float(_)),
+        };
+        // a type cannot contain itself
+        if !fits || self.resolve_all(&ty).contains(&|t| *t == Ty::Var(var)) {
+            return false;
+        }
+        self.vars[var] = Var::Bound(ty);
+        true
+    }
+
+    /// Replaces the generic types of `signature` with fresh variables,
+    /// except for those already given in `known`
+    fn instantiate(
+        &mut self,
+        signature: &Signature,
+        mut known: HashMap<String, Ty>,
+    ) -> (Vec<Ty>, Ty) {
+        let mut generics = vec![];
+        for ty in signature.params.iter().chain([&signature.ret]) {
+            ty.generics(&mut generics);
+        }
+        for name in generics {
+            if let Entry::Vacant(entry) = known.entry(name) {
+                entry.insert(self.fresh(Kind::Any));
+            }
+        }
+        let params = signature
+            .params
+            .iter()
+            .map(|ty| ty.substitute(&known))
+            .collect();
+        (params, signature.ret.substitute(&known))
+    }
+
+    /// Generic types of a struct or enum paired with the arguments of `ty`,
+    /// or with fresh variables if `ty` does not give them
+    fn generic_args(&mut self, generics: &[String], args: Option<&[Ty]>) -> HashMap<String, Ty> {
+        generics
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let arg = args
+                    .and_then(|args| args.get(i).cloned())
+                    .unwrap_or_else(|| self.fresh(Kind::Any));
+                (name.clone(), arg)
+            })
+            .collect()
+    }
+
+    fn bind_name(&mut self, name: &str, ty: Ty) {
+        match self.scopes.last_mut() {
+            Some(scope) => scope.push((name.to_string(), ty)),
+            None => {
+                self.globals.insert(name.to_string(), ty);
+            }
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<Ty> {
+        self.scopes
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev())
+            .find(|(n, _)| n == name)
+            .map(|(_, ty)| ty.clone())
+            .or_else(|| self.globals.get(name).cloned())
+    }
+
+    fn mismatch(&mut self, span: &Span, expected: &Ty, found: &Ty) {
+        let (expected, found) = (self.describe(expected), self.describe(found));
+        self.diagnostics.push(
+            Diagnostic::error("mismatched types")
+                .with_code("SL0014")
+                .with_primary(
+                    span.clone(),
+                    format!("expected `{}`, found `{}`", expected, found),
+                ),
+        );
+    }
+
+    /// Checks `expr`, reporting it if its type is not `expected`
+    fn expect(&mut self, expr: &mut Expr, expected: &Ty) {
+        let found = self.expr(expr);
+        if !self.unify(expected, &found) {
+            self.mismatch(&expr.span, expected, &found);
+        }
+    }
+
+    /// Checks `args` against `params`, reporting a call with the wrong number of them
+    fn args(&mut self, what: &str, span: &Span, args: &mut [Expr], params: &[Ty]) {
+        if args.len() != params.len() {
+            self.diagnostics.push(
+                Diagnostic::error(format!(
+                    "this {} takes {} argument{} but {} {} supplied",
+                    what,
+                    params.len(),
+                    plural(params.len()),
+                    args.len(),
+                    if args.len() == 1 { "was" } else { "were" }
+                ))
+                .with_code("SL0015")
+                .with_primary(
+                    span.clone(),
+                    format!("expected {} argument{}", params.len(), plural(params.len())),
+                ),
+            );
+            for arg in args {
+                self.expr(arg);
+            }
+            return;
+        }
+        for (arg, param) in args.iter_mut().zip(params) {
+            self.expect(arg, param);
+        }
+    }
+
+    fn function(&mut self, function: &mut Function, this: Option<Ty>) {
+        let signature = Signature::new(&function.signature);
+        self.this = this;
+        self.scopes.push(
+            function
+                .signature
+                .param_names
+                .iter()
+                .cloned()
+                .zip(signature.params.clone())
+                .collect(),
+        );
+        self.body(&mut function.body, &signature.ret);
+        for equation in &mut function.equations {
+            self.scopes.push(vec![]);
+            for (pattern, param) in equation.parameters_list.iter().zip(&signature.params) {
+                self.pattern(pattern, param);
+            }
+            if let Some(guard) = &mut equation.guard {
+                self.expect(guard, &Ty::Bool);
+            }
+            self.body(&mut equation.body, &signature.ret);
+            self.scopes.pop();
+        }
+        self.scopes.pop();
+        self.this = None;
+    }
+
+    /// Checks the body of a function, whose last expression is returned unless it returns nothing
+    fn body(&mut self, body: &mut [Expr], ret: &Ty) {
+        self.scopes.push(vec![]);
+        if let Some((last, init)) = body.split_last_mut() {
+            for expr in init {
+                self.expr(expr);
+            }
+            if *ret == Ty::unit() {
+                self.expr(last);
+            } else {
+                self.expect(last, ret);
+            }
+        }
+        self.scopes.pop();
+    }
+
+    /// Checks a block, which evaluates to its last expression
+    fn block(&mut self, body: &mut [Expr]) -> Ty {
+        self.scopes.push(vec![]);
+        let mut ty = Ty::unit();
+        for expr in body {
+            ty = self.expr(expr);
+        }
+        self.scopes.pop();
+        ty
+    }
+
+    fn literal(&mut self, literal: &Literal) -> Ty {
+        match literal {
+            Literal::Integer(_) | Literal::Unsigned(_) => self.fresh(Kind::Integer),
+            Literal::Float(_) => self.fresh(Kind::Float),
+            Literal::String(_) => Ty::String,
+            Literal::Bool(_) => Ty::Bool,
+        }
+    }
+
+    /// Checks that `pattern` can match a value of type `ty`, binding its variables
+    fn pattern(&mut self, pattern: &Pattern, ty: &Ty) {
+        match &pattern.kind {
+            PatternKind::Literal(literal) => {
+                let literal_ty = self.literal(literal);
+                if !self.unify(ty, &literal_ty) {
+                    self.mismatch(&pattern.span, ty, &literal_ty);
+                } else {
+                    let ty = self.resolve(ty);
+                    self.check_range(&pattern.span, literal, &ty, None);
+                }
+            }
+            PatternKind::Variable(name) => self.bind_name(name, ty.clone()),
+            PatternKind::List(patterns) => {
+                let item = match self.resolve(ty) {
+                    Ty::Array(item, _) => *item,
+                    _ => {
+                        let item = self.fresh(Kind::Any);
+                        let list = Ty::List(Box::new(item.clone()));
+                        if !self.unify(ty, &list) {
+                            self.mismatch(&pattern.span, ty, &list);
+                        }
+                        item
+                    }
+                };
+                for pattern in patterns {
+                    self.pattern(pattern, &item);
+                }
+            }
+            PatternKind::Tuple(patterns) => {
+                let items = patterns
+                    .iter()
+                    .map(|_| self.fresh(Kind::Any))
+                    .collect::<Vec<_>>();
+                let tuple = Ty::Tuple(items.clone());
+                if !self.unify(ty, &tuple) {
+                    self.mismatch(&pattern.span, ty, &tuple);
+                }
+                for (pattern, item) in patterns.iter().zip(&items) {
+                    self.pattern(pattern, item);
+                }
+            }
+            PatternKind::Variant(enum_name, variant, patterns) => {
+                let fields = match self.variant_fields(&pattern.span, enum_name, variant, ty) {
+                    Some(VariantInfo::Tuple(types)) => Some(types),
+                    Some(VariantInfo::Unit) => Some(vec![]),
+                    _ => None,
+                };
+                let checked = fields.is_some();
+                let fields = fields.unwrap_or_default();
+                if checked && fields.len() != patterns.len() {
+                    self.diagnostics.push(
+                        Diagnostic::error(format!(
+                            "this pattern has {} field{}, but `{}.{}` has {}",
+                            patterns.len(),
+                            plural(patterns.len()),
+                            enum_name,
+                            variant,
+                            fields.len()
+                        ))
+                        .with_code("SL0015")
+                        .with_primary(
+                            pattern.span.clone(),
+                            format!("expected {} field{}", fields.len(), plural(fields.len())),
+                        ),
+                    );
+                }
+                for (i, pattern) in patterns.iter().enumerate() {
+                    let field = fields
+                        .get(i)
+                        .cloned()
+                        .unwrap_or_else(|| self.fresh(Kind::Any));
+                    self.pattern(pattern, &field);
+                }
+            }
+            PatternKind::StructVariant(enum_name, variant, names) => {
+                let fields = match self.variant_fields(&pattern.span, enum_name, variant, ty) {
+                    Some(VariantInfo::Struct(fields)) => fields,
+                    _ => vec![],
+                };
+                for name in names {
+                    let field = fields.iter().find(|(n, _)| n == name);
+                    match field {
+                        Some((_, field)) => self.bind_name(name, field.clone()),
+                        None => {
+                            let unknown = self.fresh(Kind::Any);
+                            self.bind_name(name, unknown);
+                        }
+                    }
+                }
+            }
+            PatternKind::Wildcard => (),
+        }
+    }
+
+    /// Fields of `<enum_name>.<variant>` for a value of type `ty`, reporting
+    /// a variant that does not exist or does not belong to `ty`
+    fn variant_fields(
+        &mut self,
+        span: &Span,
+        enum_name: &str,
+        variant: &str,
+        ty: &Ty,
+    ) -> Option<VariantInfo> {
+        let generics = self.enums.get(enum_name)?.generics.clone();
+        let args = self.generic_args(&generics, None);
+        let enum_ty = Ty::Named(
+            enum_name.to_string(),
+            generics.iter().map(|name| args[name].clone()).collect(),
+        );
+        if !self.unify(ty, &enum_ty) {
+            self.mismatch(span, ty, &enum_ty);
+        }
+        let Some(info) = self.enums[enum_name].variants.get(variant) else {
+            self.diagnostics.push(
+                Diagnostic::error(format!(
+                    "no variant named `{}` in enum `{}`",
+                    variant, enum_name
+                ))
+                .with_code("SL0017")
+                .with_primary(span.clone(), "variant not found"),
+            );
+            return None;
+        };
+        Some(match info {
+            VariantInfo::Unit => VariantInfo::Unit,
+            VariantInfo::Tuple(types) => {
+                VariantInfo::Tuple(types.iter().map(|ty| ty.substitute(&args)).collect())
+            }
+            VariantInfo::Struct(fields) => VariantInfo::Struct(
+                fields
+                    .iter()
+                    .map(|(name, ty)| (name.clone(), ty.substitute(&args)))
+                    .collect(),
+            ),
+        })
+    }
+
+    /// Checks `expr` and everything in it, returning its type
+    fn expr(&mut self, expr: &mut Expr) -> Ty {
+        let ty = self.infer(expr);
+        expr.ty = Some(ty.clone());
+        ty
+    }
+
+    fn infer(&mut self, expr: &mut Expr) -> Ty {
+        let span = expr.span.clone();
+        match &mut expr.kind {
+            ExprKind::This => self.this.clone().unwrap_or_else(|| self.unknown()),
+            ExprKind::None => Ty::Option(Box::new(self.fresh(Kind::Any))),
+            ExprKind::Reference(inner) => Ty::Reference(Box::new(self.expr(inner))),
+            ExprKind::List(items) => {
+                let item = self.fresh(Kind::Any);
+                for expr in items {
+                    self.expect(expr, &item);
+                }
+                Ty::List(Box::new(item))
+            }
+            ExprKind::Tuple(items) => Ty::Tuple(items.iter_mut().map(|e| self.expr(e)).collect()),
+            ExprKind::Map(entries) => {
+                let (key, value) = (self.fresh(Kind::Any), self.fresh(Kind::Any));
+                for (k, v) in entries {
+                    self.expect(k, &key);
+                    self.expect(v, &value);
+                }
+                Ty::Map(Box::new(key), Box::new(value))
+            }
+            ExprKind::Set(items) => {
+                let item = self.fresh(Kind::Any);
+                for expr in items {
+                    self.expect(expr, &item);
+                }
+                Ty::Set(Box::new(item))
+            }
+            ExprKind::Entry { map, key } => {
+                let map_ty = self.expr(map);
+                self.entry(&map.span, &map_ty, key)
+            }
+            ExprKind::Literal(literal) => self.literal(literal),
+            ExprKind::Variable(name) => self.variable(name),
+            ExprKind::AnonParam(param) => self.expr(param),
+            ExprKind::Some(inner) => Ty::Option(Box::new(self.expr(inner))),
+            ExprKind::Ok(inner) => {
+                let inner = self.expr(inner);
+                Ty::Result(Box::new(inner), Box::new(self.fresh(Kind::Any)))
+            }
+            ExprKind::Err(inner) => {
+                let inner = self.expr(inner);
+                Ty::Result(Box::new(self.fresh(Kind::Any)), Box::new(inner))
+            }
+            ExprKind::Binary { op, lhs, rhs } => self.binary(&span, op, lhs, rhs),
+            ExprKind::Ternary {
+                condition,
+                if_true,
+                if_false,
+            } => {
+                self.expect(condition, &Ty::Bool);
+                let ty = self.expr(if_true);
+                self.expect(if_false, &ty);
+                ty
+            }
+            ExprKind::Call { callee, args } => self.call(&span, callee, args),
+            ExprKind::Variant {
+                enum_name,
+                variant,
+                args,
+            } => {
+                let enum_ty = self.fresh(Kind::Any);
+                match self.variant_fields(&span, enum_name, variant, &enum_ty) {
+                    Some(VariantInfo::Unit) => self.args("variant", &span, args, &[]),
+                    Some(VariantInfo::Tuple(types)) => self.args("variant", &span, args, &types),
+                    _ => {
+                        for arg in args {
+                            self.expr(arg);
+                        }
+                    }
+                }
+                if self.enums.contains_key(enum_name) {
+                    enum_ty
+                } else {
+                    self.unknown()
+                }
+            }
+            ExprKind::Assign {
+                pattern,
+                value,
+                type_hint,
+                ..
+            } => {
+                let ty = match type_hint {
+                    Some(hint) => {
+                        let hint = Ty::from_type(hint);
+                        self.expect(value, &hint);
+                        hint
+                    }
+                    None => self.expr(value),
+                };
+                self.pattern(pattern, &ty);
+                Ty::unit()
+            }
+            ExprKind::Closure { args, body } => {
+                let params = args
+                    .iter()
+                    .map(|_| self.fresh(Kind::Any))
+                    .collect::<Vec<_>>();
+                self.scopes
+                    .push(args.iter().cloned().zip(params.clone()).collect());
+                let ret = self.expr(body);
+                self.scopes.pop();
+                Ty::Function(params, Box::new(ret))
+            }
+            ExprKind::For {
+                pattern,
+                iterable,
+                body,
+            } => {
+                let iterable = self.expr(iterable);
+                let item = match self.resolve(&iterable) {
+                    Ty::List(item) | Ty::Array(item, _) | Ty::Set(item) | Ty::Range(item) => *item,
+                    Ty::Map(key, value) => Ty::Tuple(vec![*key, *value]),
+                    _ => self.unknown(),
+                };
+                self.loops.push(None);
+                self.scopes.push(vec![]);
+                self.pattern(pattern, &item);
+                self.block(body);
+                self.scopes.pop();
+                self.loops.pop();
+                Ty::unit()
+            }
+            ExprKind::While { condition, body } => {
+                self.expect(condition, &Ty::Bool);
+                self.loops.push(None);
+                self.block(body);
+                self.loops.pop();
+                Ty::unit()
+            }
+            ExprKind::Loop(body) => {
+                let ty = self.fresh(Kind::Any);
+                self.loops.push(Some(ty.clone()));
+                self.block(body);
+                self.loops.pop();
+                ty
+            }
+            ExprKind::Match { subject, arms } => {
+                let subject = self.expr(subject);
+                let ty = self.fresh(Kind::Any);
+                for arm in arms {
+                    self.scopes.push(vec![]);
+                    self.pattern(&arm.pattern, &subject);
+                    if let Some(guard) = &mut arm.guard {
+                        self.expect(guard, &Ty::Bool);
+                    }
+                    let arm_ty = self.block(&mut arm.body);
+                    if !self.unify(&ty, &arm_ty) {
+                        let span = arm.body.last().map_or(&arm.span, |last| &last.span);
+                        self.mismatch(&span.clone(), &ty, &arm_ty);
+                    }
+                    self.scopes.pop();
+                }
+                ty
+            }
+            ExprKind::Break(value) => {
+                let loop_ty = self.loops.last().cloned().flatten();
+                match (value, loop_ty) {
+                    (Some(value), Some(loop_ty)) => self.expect(value, &loop_ty),
+                    (Some(value), None) => {
+                        self.expr(value);
+                    }
+                    (None, Some(loop_ty)) => {
+                        if !self.unify(&loop_ty, &Ty::unit()) {
+                            self.mismatch(&span, &loop_ty, &Ty::unit());
+                        }
+                    }
+                    (None, None) => (),
+                }
+                // `break` never evaluates to anything, so it fits wherever it is
+                self.fresh(Kind::Any)
+            }
+            ExprKind::Continue => self.fresh(Kind::Any),
+        }
+    }
+
+    fn variable(&mut self, name: &str) -> Ty {
+        if let Some(ty) = self.lookup(name) {
+            return ty;
+        }
+        match self.functions.get(name).cloned() {
+            Some(signature) => {
+                let (params, ret) = self.instantiate(&signature, HashMap::new());
+                Ty::Function(params, Box::new(ret))
+            }
+            None => self.unknown(),
+        }
+    }
+
+    fn call(&mut self, span: &Span, callee: &str, args: &mut [Expr]) -> Ty {
+        // a closure in a variable or parameter
+        if let Some(ty) = self.lookup(callee) {
+            match self.resolve(&ty) {
+                Ty::Function(params, ret) => {
+                    self.args("function", span, args, &params);
+                    return *ret;
+                }
+                Ty::Var(_) => {
+                    let params = args.iter_mut().map(|arg| self.expr(arg)).collect();
+                    let ret = self.fresh(Kind::Any);
+                    self.unify(&ty, &Ty::Function(params, Box::new(ret.clone())));
+                    return ret;
+                }
+                _ => {
+                    for arg in args {
+                        self.expr(arg);
+                    }
+                    let ty = self.describe(&ty);
+                    self.diagnostics.push(
+                        Diagnostic::error(format!("cannot call `{}` of type `{}`", callee, ty))
+                            .with_code("SL0018")
+                            .with_primary(span.clone(), "not a function"),
+                    );
+                    return self.unknown();
+                }
+            }
+        }
+        if let Some(signature) = self.functions.get(callee).cloned() {
+            let (params, ret) = self.instantiate(&signature, HashMap::new());
+            self.args("function", span, args, &params);
+            return ret;
+        }
+        for arg in args.iter_mut() {
+            self.expr(arg);
+        }
+        match self.structs.get(callee).map(|s| s.generics.clone()) {
+            Some(generics) => {
+                let args = self.generic_args(&generics, None);
+                Ty::Named(
+                    callee.to_string(),
+                    generics.iter().map(|name| args[name].clone()).collect(),
+                )
+            }
+            None => self.unknown(),
+        }
+    }
+
+    /// Type of `<map>{<key>}` where `map` is of type `map_ty`
+    fn entry(&mut self, span: &Span, map_ty: &Ty, key: &mut Expr) -> Ty {
+        match self.resolve(map_ty) {
+            Ty::Map(key_ty, value) => {
+                self.expect(key, &key_ty);
+                *value
+            }
+            Ty::Var(_) => {
+                let key_ty = self.expr(key);
+                let value = self.fresh(Kind::Any);
+                let map = Ty::Map(Box::new(key_ty), Box::new(value.clone()));
+                self.unify(map_ty, &map);
+                value
+            }
+            _ => {
+                self.expr(key);
+                let map_ty = self.describe(map_ty);
+                self.diagnostics.push(
+                    Diagnostic::error(format!("cannot look up an entry of `{}`", map_ty))
+                        .with_code("SL0018")
+                        .with_primary(span.clone(), format!("this is `{}`", map_ty))
+                        .with_note("only maps have entries"),
+                );
+                self.unknown()
+            }
+        }
+    }
+
+    fn binary(&mut self, span: &Span, op: &str, lhs: &mut Expr, rhs: &mut Expr) -> Ty {
+        match op {
+            "." => {
+                let lhs = self.expr(lhs);
+                self.member(&lhs, rhs)
+            }
+            "<<" | "<|" => {
+                let lhs = self.expr(lhs);
+                match self.resolve(&lhs) {
+                    // `<list> << <item>` pushes the item onto the list
+                    Ty::List(item) if op == "<<" => self.expect(rhs, &item),
+                    _ => {
+                        self.expr(rhs);
+                    }
+                }
+                Ty::unit()
+            }
+            "==" | "!=" | "<" | "<=" | ">" | ">=" => {
+                let lhs = self.expr(lhs);
+                self.expect(rhs, &lhs);
+                Ty::Bool
+            }
+            "&&" | "||" => {
+                self.expect(lhs, &Ty::Bool);
+                self.expect(rhs, &Ty::Bool);
+                Ty::Bool
+            }
+            ".." => {
+                let lhs = self.expr(lhs);
+                self.expect(rhs, &lhs);
+                self.operand(span, op, &lhs);
+                Ty::Range(Box::new(lhs))
+            }
+            "=" => {
+                let lhs = self.expr(lhs);
+                self.expect(rhs, &lhs);
+                Ty::unit()
+            }
+            "+=" | "-=" | "*=" | "/=" | "%=" | "**=" | "&=" | "|=" | "^=" => {
+                let lhs = self.expr(lhs);
+                self.expect(rhs, &lhs);
+                self.operand(span, op, &lhs);
+                Ty::unit()
+            }
+            "+" | "-" | "*" | "/" | "%" | "**" | "&" | "|" | "^" => {
+                let lhs = self.expr(lhs);
+                self.expect(rhs, &lhs);
+                self.operand(span, op, &lhs);
+                lhs
+            }
+            _ => {
+                self.expr(lhs);
+                self.expr(rhs);
+                if is_assignment(op) {
+                    Ty::unit()
+                } else {
+                    self.unknown()
+                }
+            }
+        }
+    }
+
+    /// Reports operands of type `ty` that `op` cannot be used on
+    fn operand(&mut self, span: &Span, op: &str, ty: &Ty) {
+        let fits = match self.resolve(ty) {
+            Ty::Var(_) | Ty::Generic(_) | Ty::Named(..) => true,
+            Ty::Unsigned(_) | Ty::Integer(_) => true,
+            Ty::Float(_) => !matches!(op, "&" | "|" | "^" | "&=" | "|=" | "^=" | ".."),
+            Ty::String => matches!(op, "+" | "+="),
+            Ty::Bool => matches!(op, "&" | "|" | "^" | "&=" | "|=" | "^="),
+            Ty::Set(_) => matches!(op, "&" | "|" | "^"),
+            _ => false,
+        };
+        if !fits {
+            let ty = self.describe(ty);
+            self.diagnostics.push(
+                Diagnostic::error(format!("cannot use `{}` on `{}`", op, ty))
+                    .with_code("SL0018")
+                    .with_primary(
+                        span.clone(),
+                        format!("`{}` cannot be applied to `{}`", op, ty),
+                    ),
+            );
+        }
+    }
+
+    /// Type of `<lhs>.<member>` where `lhs` is of type `lhs_ty`
+    fn member(&mut self, lhs_ty: &Ty, member: &mut Expr) -> Ty {
+        let span = member.span.clone();
+        let resolved = self.resolve(lhs_ty);
+        let ty = match &mut member.kind {
+            ExprKind::Variable(name) => match &resolved {
+                Ty::Named(struct_name, args) if self.structs.contains_key(struct_name) => {
+                    let info = &self.structs[struct_name];
+                    let field = info.fields.iter().find(|(n, _)| n == name).cloned();
+                    let generics = info.generics.clone();
+                    let args = self.generic_args(&generics, Some(args));
+                    match field {
+                        Some((_, field)) => field.substitute(&args),
+                        None => {
+                            self.diagnostics.push(
+                                Diagnostic::error(format!(
+                                    "no field `{}` on type `{}`",
+                                    name, struct_name
+                                ))
+                                .with_code("SL0017")
+                                .with_primary(span, "unknown field"),
+                            );
+                            self.unknown()
+                        }
+                    }
+                }
+                _ => self.unknown(),
+            },
+            ExprKind::Call { callee, args } => {
+                let method = match &resolved {
+                    Ty::Named(struct_name, type_args) => {
+                        self.structs.get(struct_name).and_then(|info| {
+                            let signature = info.methods.get(callee)?.clone();
+                            Some((info.generics.clone(), type_args.clone(), signature))
+                        })
+                    }
+                    _ => None,
+                };
+                match method {
+                    Some((generics, type_args, signature)) => {
+                        let known = self.generic_args(&generics, Some(&type_args));
+                        let (params, ret) = self.instantiate(&signature, known);
+                        self.args("method", &span, args, &params);
+                        ret
+                    }
+                    // methods from Rust, such as `len`
+                    None => {
+                        for arg in args {
+                            self.expr(arg);
+                        }
+                        self.unknown()
+                    }
+                }
+            }
+            ExprKind::Entry { map, key } => {
+                let map_ty = self.member(lhs_ty, map);
+                self.entry(&map.span, &map_ty, key)
+            }
+            ExprKind::Literal(Literal::Integer(i)) => match &resolved {
+                Ty::Tuple(types) if (*i as usize) < types.len() => types[*i as usize].clone(),
+                _ => self.unknown(),
+            },
+            _ => return self.expr(member),
+        };
+        member.ty = Some(ty.clone());
+        ty
+    }
+
+    /// Settles the types left in `expr` once everything has been checked,
+    /// making integers and floats that nothing constrains `Z64` and `R64`
+    fn finish(&mut self, expr: &mut Expr) {
+        if let Some(ty) = &expr.ty {
+            expr.ty = Some(self.settle(&ty.clone()));
+        }
+        // evaluated constants are literals that were never written, so name the constant instead
+        if let ExprKind::Assign {
+            pattern,
+            assign_type: AssignType::Const | AssignType::Static,
+            value,
+            ..
+        } = &mut expr.kind
+            && let PatternKind::Variable(name) = &pattern.kind
+            && let ExprKind::Literal(literal) = &value.kind
+        {
+            value.ty = value.ty.clone().map(|ty| self.settle(&ty));
+            if let Some(ty) = &value.ty {
+                self.check_range(&value.span, literal, ty, Some(name));
+            }
+            return;
+        }
+        if let ExprKind::Literal(literal) = &expr.kind
+            && let Some(ty) = &expr.ty
+        {
+            self.check_range(&expr.span, literal, ty, None);
+        }
+        for child in expr.children_mut() {
+            self.finish(child);
+        }
+    }
+
+    /// Reports an integer `literal` at `span` that does not fit in `ty`,
+    /// naming the `constant` it is the value of if there is one
+    fn check_range(&mut self, span: &Span, literal: &Literal, ty: &Ty, constant: Option<&str>) {
+        let Some((min, max)) = ty.integer_range() else {
+            return;
+        };
+        let fits = match literal {
+            Literal::Integer(value) => *value >= min && (*value < 0 || *value as u128 <= max),
+            Literal::Unsigned(value) => *value <= max,
+            _ => return,
+        };
+        if fits {
+            return;
+        }
+        let (message, label) = match constant {
+            Some(name) => (
+                format!("value of `{}` is out of range for `{}`", name, ty),
+                format!(
+                    "`{}` is `{}`, which does not fit in `{}`",
+                    name,
+                    literal.to_rust(),
+                    ty
+                ),
+            ),
+            None => (
+                format!("literal out of range for `{}`", ty),
+                format!("`{}` does not fit in `{}`", literal.to_rust(), ty),
+            ),
+        };
+        self.diagnostics.push(
+            Diagnostic::error(message)
+                .with_code("SL0026")
+                .with_primary(span.clone(), label)
+                .with_note(format!("`{}` holds values from `{}` to `{}`", ty, min, max)),
+        );
+    }
+
+    fn settle(&mut self, ty: &Ty) -> Ty {
+        ty.map(&mut |t| {
+            let Ty::Var(_) = t else {
+                return None;
+            };
+            let resolved = self.resolve(t);
+            let Ty::Var(var) = resolved else {
+                return Some(self.settle(&resolved));
+            };
+            let default = match self.vars[var] {
+                Var::Unbound {
+                    kind: Kind::Integer,
+                    opaque: false,
+                } => Ty::Integer(64),
+                Var::Unbound {
+                    kind: Kind::Float,
+                    opaque: false,
+                } => Ty::Float(64),
+                _ => return Some(resolved),
+            };
+            self.vars[var] = Var::Bound(default.clone());
+            Some(default)
+        })
+    }
+}
+
+fn plural(n: usize) -> &'static str {
+    if n == 1 { "" } else { "s" }
+}
+
+fn fields(fields: &[Field]) -> Vec<(String, Ty)> {
+    fields
+        .iter()
+        .map(|field| (field.name.clone(), Ty::from_type(&field.r#type)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::{parser, span::SourceFile};
+
+    /// The Rust type codegen writes for every constant and static in `source`,
+    /// followed by the ones of the bindings in its `main` function
+    fn inferred(source: &str) -> Vec<String> {
+        let file = Rc::new(SourceFile::new("test.sl", source));
+        let (mut program, diagnostics) = parser::parse::<false>(file);
+        assert!(diagnostics.is_empty());
+        assert!(check(&mut program).is_empty());
+        let main = program
+            .functions
+            .iter()
+            .find(|f| f.signature.name == "main");
+        program
+            .variables
+            .iter()
+            .chain(main.into_iter().flat_map(|f| &f.body))
+            .filter_map(|expr| match &expr.kind {
+                ExprKind::Assign { value, .. } => {
+                    Some(value.ty.as_ref().map_or(String::from("_"), Ty::to_rust))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(
+            inferred(
+                "main args: [String]\n\ta := 1\n\tb := 1.5\n\tc := \"soul\"\n\td := [1 2]\n\te := {\"a\" => 1}\n\tf := (1 \"b\")\n"
+            ),
+            [
+                "i64",
+                "f64",
+                "String",
+                "Vec<i64>",
+                "std::collections::HashMap<String,i64>",
+                "(i64,String)"
+            ]
+        );
+    }
+
+    #[test]
+    fn calls() {
+        assert_eq!(
+            inferred(
+                "half n: R32 -> R32\n\tn / 2.0\n\nsmall n: N8 -> N8\n\tn\n\nmain args: [String]\n\ta := half(1.0)\n\tb := small(3)\n\tc := args.len()\n"
+            ),
+            ["f32", "u8", "_"]
+        );
+    }
+
+    #[test]
+    fn binaries() {
+        assert_eq!(
+            inferred(
+                "size n: N16 -> N16\n\tn\n\nmain args: [String]\n\ta := 1 + size(2)\n\tb := a < 3\n\tc := 1.5 * 2.0\n\td := 0..a\n"
+            ),
+            ["u16", "bool", "f64", "std::ops::Range<u16>"]
+        );
+    }
+
+    #[test]
+    fn constants_and_statics() {
+        assert_eq!(
+            inferred("LIMIT := 10\nSCALE: R32 = 2.0\nname := \"soulite\"\ncount ;= 10 * 2\n"),
+            ["i64", "f32", "String", "i64"]
+        );
+    }
+}
@@ -0,0 +1,118 @@
+//! Compiles the Soulite samples in `test` through rustc, and every file in `test/fail`
+//! checking that it reports exactly the diagnostics listed in its `\\ expect:` comments,
+//! such as `\\ expect: SL0001 SLW0003`
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How long a single file may take before the compiler is considered stuck
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Samples written in syntax the compiler does not support yet
+const UNSUPPORTED: &[&str] = &["func.sl", "mixed.sl"];
+
+#[test]
+fn pass() {
+    let failures = sources("test")
+        .iter()
+        .filter(|path| !UNSUPPORTED.contains(&&*path.file_name().unwrap().to_string_lossy()))
+        .filter_map(|path| {
+            let (success, stderr) = compile(path, "pass", true);
+            (!success).then(|| format!("{}: failed to compile\n{}", path.display(), stderr))
+        })
+        .collect::<Vec<_>>();
+    assert!(failures.is_empty(), "\n{}", failures.join("\n\n"));
+}
+
+#[test]
+fn fail() {
+    let failures = sources("test/fail")
+        .iter()
+        .filter_map(|path| check(path).err())
+        .collect::<Vec<_>>();
+    assert!(failures.is_empty(), "\n{}", failures.join("\n\n"));
+}
+
+/// Lists the Soulite files in `dir`, relative to the crate root
+fn sources(dir: &str) -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(dir);
+    let mut paths = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "sl"))
+        .collect::<Vec<_>>();
+    paths.sort();
+    assert!(!paths.is_empty(), "no Soulite files in {}", dir.display());
+    paths
+}
+
+/// Compiles the file at `path` with JSON diagnostics, returning whether it succeeded and its stderr,
+/// and going on to build an executable with rustc if `executable` is set
+fn compile(path: &Path, kind: &str, executable: bool) -> (bool, String) {
+    let stem = path.file_stem().unwrap().to_string_lossy();
+    let output = std::env::temp_dir().join(format!("soulite-{}-{}", kind, stem));
+    // stderr goes to a file so a chatty compiler cannot block on a full pipe
+    let log = output.with_extension("log");
+    let mut command = Command::new(env!("CARGO_BIN_EXE_soulite"));
+    if executable {
+        command.arg("-c").arg(&output);
+    }
+    let mut child = command
+        .arg("--message-format=json")
+        .arg("-t")
+        .arg(output.with_extension("rs"))
+        .arg(path)
+        .stdout(Stdio::null())
+        .stderr(fs::File::create(&log).unwrap())
+        .spawn()
+        .unwrap();
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+        if start.elapsed() > TIMEOUT {
+            child.kill().unwrap();
+            return (false, format!("timed out after {:?}", TIMEOUT));
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    (status.success(), fs::read_to_string(&log).unwrap())
+}
+
+/// Compiles the file at `path`, comparing its diagnostic codes to the expected ones
+fn check(path: &Path) -> Result<(), String> {
+    let source = fs::read_to_string(path).unwrap();
+    let mut expected = source
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("\\\\ expect:"))
+        .flat_map(str::split_whitespace)
+        .map(String::from)
+        .collect::<Vec<_>>();
+    let (_, stderr) = compile(path, "fail", false);
+    let mut found = stderr.lines().filter_map(code).collect::<Vec<_>>();
+    expected.sort();
+    found.sort();
+    if expected == found {
+        Ok(())
+    } else {
+        Err(format!(
+            "{}: expected {:?}, found {:?}\n{}",
+            path.display(),
+            expected,
+            found,
+            stderr
+        ))
+    }
+}
+
+/// Reads the code of a diagnostic printed as JSON
+fn code(line: &str) -> Option<String> {
+    let (_, rest) = line.split_once("\"code\":\"")?;
+    rest.split_once('"').map(|(code, _)| code.to_string())
+}